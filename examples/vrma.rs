@@ -49,5 +49,6 @@ fn apply_play_vrma(
     commands.entity(vrma_entity).trigger(PlayVrma {
         repeat: RepeatAnimation::Forever,
         transition_duration: Duration::ZERO,
+        ..default()
     });
 }
@@ -84,5 +84,6 @@ fn play_vrma<const I: usize>(
     commands.entity(vrma_entity).trigger(PlayVrma {
         repeat: RepeatAnimation::Forever,
         transition_duration: Duration::from_millis(300),
+        ..default()
     });
 }
@@ -17,11 +17,16 @@ pub mod prelude {
 #[doc(hidden)]
 #[cfg(test)]
 pub(crate) mod tests {
+    use crate::prelude::{Vrm, VrmaClip, VrmaClips};
+    use crate::vrma::VrmAnimationNodeIndices;
     use bevy::MinimalPlugins;
     use bevy::asset::AssetPlugin;
-    use bevy::prelude::ImagePlugin;
+    use bevy::ecs::system::BoxedSystem;
+    use bevy::prelude::*;
+    use bevy::render::MainWorld;
     use bevy::render::camera::CameraPlugin;
     use bevy::window::WindowPlugin;
+    use std::time::Duration;
 
     pub type TestResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
 
@@ -43,4 +48,94 @@ pub(crate) mod tests {
         ));
         app
     }
+
+    /// The entities spawned by [`spawn_vrm_with_vrmas`].
+    pub struct TestVrm {
+        pub vrm: Entity,
+        pub root_bone: Entity,
+        pub vrmas: Vec<Entity>,
+    }
+
+    /// Spawns a VRM whose root bone plays `graph`, and a VRMA with a clip of one second for each node.
+    ///
+    /// The other components such as [`VrmAnimationClipHandles`](crate::vrma::VrmAnimationClipHandles)
+    /// are inserted by each test.
+    pub fn spawn_vrm_with_vrmas(
+        app: &mut App,
+        graph: Handle<AnimationGraph>,
+        nodes: &[AnimationNodeIndex],
+    ) -> TestVrm {
+        let vrm = app.world_mut().spawn_empty().id();
+        let vrmas = nodes
+            .iter()
+            .map(|node| {
+                app.world_mut()
+                    .spawn((
+                        VrmAnimationNodeIndices(vec![*node]),
+                        VrmaClips(vec![VrmaClip {
+                            name: None,
+                            duration: Duration::from_secs(1),
+                            markers: Vec::new(),
+                        }]),
+                        ChildOf(vrm),
+                    ))
+                    .id()
+            })
+            .collect();
+        let root_bone = app
+            .world_mut()
+            .spawn((
+                Name::new(Vrm::ROOT_BONE),
+                Transform::default(),
+                AnimationPlayer::default(),
+                AnimationTransitions::default(),
+                AnimationGraphHandle(graph),
+                ChildOf(vrm),
+            ))
+            .id();
+        TestVrm {
+            vrm,
+            root_bone,
+            vrmas,
+        }
+    }
+
+    /// Runs an extract system in a render world with the world of the app as [`MainWorld`],
+    /// like `ExtractSchedule` does.
+    pub struct TestExtract {
+        pub render_world: World,
+        system: BoxedSystem,
+        initialized: bool,
+    }
+
+    impl TestExtract {
+        pub fn new<M>(system: impl IntoSystem<(), (), M>) -> Self {
+            let mut render_world = World::new();
+            render_world.insert_resource(MainWorld::default());
+            Self {
+                render_world,
+                system: Box::new(IntoSystem::into_system(system)),
+                initialized: false,
+            }
+        }
+
+        pub fn run(
+            &mut self,
+            app: &mut App,
+        ) {
+            std::mem::swap(
+                app.world_mut(),
+                &mut self.render_world.resource_mut::<MainWorld>(),
+            );
+            if !self.initialized {
+                self.system.initialize(&mut self.render_world);
+                self.initialized = true;
+            }
+            self.system.run((), &mut self.render_world);
+            std::mem::swap(
+                app.world_mut(),
+                &mut self.render_world.resource_mut::<MainWorld>(),
+            );
+        }
+    }
 }
@@ -1,11 +1,12 @@
 mod cameras;
 mod child_searcher;
+mod mtoon_materials;
 mod parent_searcher;
 mod vrm_animation;
 
 pub mod prelude {
     pub use crate::system_param::{
-        cameras::Cameras, child_searcher::ChildSearcher, parent_searcher::ParentSearcher,
-        vrm_animation::VrmAnimation,
+        cameras::Cameras, child_searcher::ChildSearcher, mtoon_materials::MToonMaterials,
+        parent_searcher::ParentSearcher, vrm_animation::VrmAnimation,
     };
 }
@@ -0,0 +1,290 @@
+use crate::prelude::MToonMaterial;
+use bevy::color::ColorToComponents;
+use bevy::ecs::system::SystemParam;
+use bevy::gltf::GltfMaterialName;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+/// The marker that the material handle of the mesh is unique to the VRM instance.
+///
+/// It holds the id of the cloned asset, so that the material is cloned again
+/// if the handle is replaced from the outside.
+#[derive(Component)]
+struct InstanceMToonMaterial(AssetId<MToonMaterial>);
+
+/// Finds and edits the [`MToonMaterial`]s of a VRM instance.
+///
+/// The materials are looked up by the glTF material name.
+/// Editing is clone-on-write: the first edit clones the material into an asset unique to the VRM instance,
+/// so that the other VRMs spawned from the same asset are not affected.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn blush(
+///     In(vrm): In<Entity>,
+///     mut materials: MToonMaterials,
+/// ) {
+///     materials.edit(vrm, "Face", |material| {
+///         material.shade.color = LinearRgba::rgb(0.9, 0.5, 0.5);
+///     });
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct MToonMaterials<'w, 's> {
+    commands: Commands<'w, 's>,
+    assets: ResMut<'w, Assets<MToonMaterial>>,
+    childrens: Query<'w, 's, &'static Children>,
+    meshes: Query<
+        'w,
+        's,
+        (
+            &'static mut MeshMaterial3d<MToonMaterial>,
+            &'static GltfMaterialName,
+            Option<&'static InstanceMToonMaterial>,
+        ),
+    >,
+    /// The ids of the materials cloned by this system.
+    ///
+    /// [`InstanceMToonMaterial`] is inserted through [`Commands`], so it is not visible to the later edits in the same system.
+    /// The ids of the removed assets are dropped on every edit.
+    instanced: Local<'s, HashSet<AssetId<MToonMaterial>>>,
+}
+
+impl MToonMaterials<'_, '_> {
+    /// Returns the glTF material names of the [`MToonMaterial`]s used in the VRM.
+    pub fn names(
+        &self,
+        vrm: Entity,
+    ) -> Vec<String> {
+        let mut names = Vec::new();
+        for entity in self.childrens.iter_descendants(vrm) {
+            if let Ok((_, name, _)) = self.meshes.get(entity)
+                && !names.contains(&name.0)
+            {
+                names.push(name.0.clone());
+            }
+        }
+        names
+    }
+
+    /// Returns the mesh entities that use the material with the given name.
+    pub fn entities(
+        &self,
+        vrm: Entity,
+        material_name: &str,
+    ) -> Vec<Entity> {
+        self.childrens
+            .iter_descendants(vrm)
+            .filter(|entity| {
+                self.meshes
+                    .get(*entity)
+                    .is_ok_and(|(_, name, _)| name.0 == material_name)
+            })
+            .collect()
+    }
+
+    /// Returns the material with the given name.
+    pub fn get(
+        &self,
+        vrm: Entity,
+        material_name: &str,
+    ) -> Option<&MToonMaterial> {
+        let entity = *self.entities(vrm, material_name).first()?;
+        let (handle, _, _) = self.meshes.get(entity).ok()?;
+        self.assets.get(handle.id())
+    }
+
+    /// Edits the material with the given name.
+    ///
+    /// Returns `false` if the VRM has no material with the name.
+    pub fn edit(
+        &mut self,
+        vrm: Entity,
+        material_name: &str,
+        mut f: impl FnMut(&mut MToonMaterial),
+    ) -> bool {
+        let entities = self.entities(vrm, material_name);
+        self.edit_entities(&entities, &mut f)
+    }
+
+    /// Edits all [`MToonMaterial`]s of the VRM.
+    pub fn edit_all(
+        &mut self,
+        vrm: Entity,
+        mut f: impl FnMut(&mut MToonMaterial),
+    ) {
+        let entities = self
+            .childrens
+            .iter_descendants(vrm)
+            .filter(|entity| self.meshes.contains(*entity))
+            .collect::<Vec<_>>();
+        self.edit_entities(&entities, &mut f);
+    }
+
+    /// Multiplies the base color and the shade color of all materials of the VRM by the given color.
+    pub fn tint(
+        &mut self,
+        vrm: Entity,
+        color: impl Into<LinearRgba>,
+    ) {
+        let color = color.into();
+        self.edit_all(vrm, |material| {
+            material.base_color =
+                LinearRgba::from_vec4(material.base_color.to_linear().to_vec4() * color.to_vec4())
+                    .into();
+            material.shade.color =
+                LinearRgba::from_vec3(material.shade.color.to_vec3() * color.to_vec3());
+        });
+    }
+
+    /// Multiplies the outline width of all materials of the VRM by the given scale.
+    pub fn scale_outline_width(
+        &mut self,
+        vrm: Entity,
+        scale: f32,
+    ) {
+        self.edit_all(vrm, |material| {
+            material.outline.width_factor *= scale;
+        });
+    }
+
+    fn edit_entities(
+        &mut self,
+        entities: &[Entity],
+        f: &mut impl FnMut(&mut MToonMaterial),
+    ) -> bool {
+        let assets = &self.assets;
+        self.instanced.retain(|id| assets.contains(*id));
+        // The meshes sharing a material keep sharing the cloned one.
+        let mut cloned = HashMap::<AssetId<MToonMaterial>, Handle<MToonMaterial>>::new();
+        for entity in entities {
+            let Ok((mut handle, _, instance)) = self.meshes.get_mut(*entity) else {
+                continue;
+            };
+            if instance.is_some_and(|instance| instance.0 == handle.id())
+                || self.instanced.contains(&handle.id())
+            {
+                continue;
+            }
+            let new_handle = match cloned.get(&handle.id()) {
+                Some(new_handle) => new_handle.clone(),
+                None => {
+                    let Some(material) = self.assets.get(handle.id()).cloned() else {
+                        continue;
+                    };
+                    let new_handle = self.assets.add(material);
+                    cloned.insert(handle.id(), new_handle.clone());
+                    self.instanced.insert(new_handle.id());
+                    new_handle
+                }
+            };
+            self.commands
+                .entity(*entity)
+                .insert(InstanceMToonMaterial(new_handle.id()));
+            handle.0 = new_handle;
+        }
+
+        let mut edited = Vec::new();
+        for entity in entities {
+            let Ok((handle, _, _)) = self.meshes.get(*entity) else {
+                continue;
+            };
+            if edited.contains(&handle.id()) {
+                continue;
+            }
+            if let Some(material) = self.assets.get_mut(handle.id()) {
+                f(material);
+                edited.push(handle.id());
+            }
+        }
+        !edited.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestResult, test_app};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::gltf::GltfMaterialName;
+    use bevy::prelude::*;
+
+    #[test]
+    fn edit_does_not_affect_other_instances() -> TestResult {
+        let mut app = test_app();
+        app.init_asset::<MToonMaterial>();
+        let shared = app
+            .world_mut()
+            .resource_mut::<Assets<MToonMaterial>>()
+            .add(MToonMaterial::default());
+        let spawn_vrm = |world: &mut World| {
+            world
+                .spawn_empty()
+                .with_child((
+                    MeshMaterial3d(shared.clone()),
+                    GltfMaterialName("Face".to_string()),
+                ))
+                .id()
+        };
+        let vrm1 = spawn_vrm(app.world_mut());
+        let vrm2 = spawn_vrm(app.world_mut());
+
+        let edited = app
+            .world_mut()
+            .run_system_once(move |mut materials: MToonMaterials| {
+                materials.edit(vrm1, "Face", |material| {
+                    material.shade.color = LinearRgba::RED;
+                })
+            })?;
+        assert!(edited);
+        app.world_mut()
+            .run_system_once(move |materials: MToonMaterials| {
+                assert_eq!(
+                    materials.get(vrm1, "Face").map(|m| m.shade.color),
+                    Some(LinearRgba::RED)
+                );
+                assert_eq!(
+                    materials.get(vrm2, "Face").map(|m| m.shade.color),
+                    Some(LinearRgba::BLACK)
+                );
+            })?;
+        Ok(())
+    }
+
+    #[test]
+    fn clone_once_in_multiple_edits_of_same_system() -> TestResult {
+        let mut app = test_app();
+        app.init_asset::<MToonMaterial>();
+        let shared = app
+            .world_mut()
+            .resource_mut::<Assets<MToonMaterial>>()
+            .add(MToonMaterial::default());
+        let vrm = app
+            .world_mut()
+            .spawn_empty()
+            .with_child((
+                MeshMaterial3d(shared.clone()),
+                GltfMaterialName("Face".to_string()),
+            ))
+            .id();
+
+        app.world_mut()
+            .run_system_once(move |mut materials: MToonMaterials| {
+                materials.tint(vrm, LinearRgba::RED);
+                materials.scale_outline_width(vrm, 2.);
+            })?;
+        assert_eq!(app.world().resource::<Assets<MToonMaterial>>().len(), 2);
+        app.world_mut()
+            .run_system_once(move |materials: MToonMaterials| {
+                let material = materials.get(vrm, "Face").unwrap();
+                assert_eq!(material.base_color, Color::from(LinearRgba::RED));
+                assert_eq!(
+                    material.outline.width_factor,
+                    MToonMaterial::default().outline.width_factor * 2.
+                );
+            })?;
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ mod initialize;
 mod loader;
 mod look_at;
 mod mtoon;
+mod render_layers;
 pub(crate) mod spring_bone;
 
 use crate::macros::marker_component;
@@ -13,6 +14,7 @@ use crate::vrm::humanoid_bone::VrmHumanoidBonePlugin;
 use crate::vrm::initialize::VrmInitializePlugin;
 use crate::vrm::loader::{VrmAsset, VrmLoaderPlugin};
 use crate::vrm::look_at::LookAtPlugin;
+use crate::vrm::render_layers::VrmRenderLayersPlugin;
 use crate::vrm::spring_bone::VrmSpringBonePlugin;
 use bevy::app::{App, Plugin};
 use bevy::asset::AssetApp;
@@ -30,6 +32,7 @@ pub mod prelude {
         loader::{VrmAsset, VrmHandle},
         look_at::LookAt,
         mtoon::prelude::*,
+        spring_bone::self_collision::SpringSelfCollision,
     };
 }
 
@@ -110,6 +113,7 @@ impl Plugin for VrmPlugin {
             VrmExpressionPlugin,
             MtoonMaterialPlugin,
             LookAtPlugin,
+            VrmRenderLayersPlugin,
         ));
 
         app.register_type::<Vrm>()
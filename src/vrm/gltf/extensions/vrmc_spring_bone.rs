@@ -84,6 +84,17 @@ pub struct SpringJoint {
     #[serde(rename = "hitRadius")]
     pub hit_radius: Option<f32>,
     pub stiffness: Option<f32>,
+    pub extensions: Option<SpringJointExtensions>,
+}
+
+/// The extensions of [`SpringJoint`].
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default)]
+pub struct SpringJointExtensions {
+    /// The angle limit of the joint.
+    ///
+    /// This is not part of the VRM specification, but a custom extension of this crate.
+    #[serde(rename = "BEVY_vrm1_springJointLimit")]
+    pub limit: Option<SpringJointLimit>,
 }
 
 /// The shape of the collision detection for [Collider]
@@ -162,11 +173,97 @@ pub struct Capsule {
     pub tail: [f32; 3],
 }
 
+/// Limits the swing of a spring joint relative to its initial local rotation.
+///
+/// Inserted into the joint entity automatically if the VRM declares the limit
+/// in the `BEVY_vrm1_springJointLimit` extension of the joint,
+/// and it can also be inserted, changed or removed at runtime.
+///
+/// ```json
+/// "extensions": {
+///     "BEVY_vrm1_springJointLimit": {
+///         "cone": { "angle": 0.5 }
+///     }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Component, Reflect)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpringJointLimit {
+    /// Limits the swing to a cone around the initial direction of the bone.
+    Cone(ConeLimit),
+    /// Limits the swing to a rotation around a single axis.
+    Hinge(HingeLimit),
+}
+
+impl SpringJointLimit {
+    /// Clamps the direction of the bone so that it stays within the limit.
+    ///
+    /// Both `bone_axis` and `dir` are expressed in the initial local space of the joint.
+    pub fn clamp(
+        &self,
+        bone_axis: Vec3,
+        dir: Vec3,
+    ) -> Vec3 {
+        match self {
+            Self::Cone(cone) => {
+                if bone_axis.angle_between(dir) <= cone.angle {
+                    return dir;
+                }
+                let (axis, _) = Quat::from_rotation_arc(bone_axis, dir).to_axis_angle();
+                Quat::from_axis_angle(axis, cone.angle) * bone_axis
+            }
+            Self::Hinge(hinge) => {
+                let axis = Vec3::from(hinge.axis).normalize_or_zero();
+                let from = bone_axis.reject_from_normalized(axis);
+                let to = dir.reject_from_normalized(axis);
+                if axis == Vec3::ZERO || from == Vec3::ZERO || to == Vec3::ZERO {
+                    return bone_axis;
+                }
+                let angle = axis.dot(from.cross(to)).atan2(from.dot(to));
+                Quat::from_axis_angle(axis, angle.clamp(hinge.min, hinge.max)) * bone_axis
+            }
+        }
+    }
+}
+
+/// The cone limit of [`SpringJointLimit::Cone`].
+///
+/// The bone can swing freely in any direction as long as the angle from its initial direction
+/// does not exceed [`ConeLimit::angle`].
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Reflect, Default)]
+#[reflect(Serialize, Deserialize)]
+pub struct ConeLimit {
+    /// The maximum angle in radians between the bone and its initial direction.
+    pub angle: f32,
+}
+
+/// The hinge limit of [`SpringJointLimit::Hinge`].
+///
+/// The bone is projected onto the plane perpendicular to [`HingeLimit::axis`],
+/// and its signed angle from the initial direction is clamped to `min..=max`.
+/// The angle is positive when rotating counterclockwise around the axis (right-hand rule).
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Reflect, Default)]
+#[reflect(Serialize, Deserialize)]
+pub struct HingeLimit {
+    /// The rotation axis of the hinge in the initial local space of the joint.
+    /// It doesn't need to be normalized.
+    pub axis: [f32; 3],
+    /// The minimum rotation angle around the axis in radians.
+    pub min: f32,
+    /// The maximum rotation angle around the axis in radians.
+    pub max: f32,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::success;
     use crate::tests::TestResult;
-    use crate::vrm::gltf::extensions::vrmc_spring_bone::VRMCSpringBone;
+    use crate::vrm::gltf::extensions::vrmc_spring_bone::{
+        ConeLimit, HingeLimit, SpringJoint, SpringJointLimit, VRMCSpringBone,
+    };
+    use bevy::math::Vec3;
+    use std::f32::consts::FRAC_PI_4;
 
     #[test]
     fn deserialize_vrmc_spring_bone() -> TestResult {
@@ -174,4 +271,50 @@ mod tests {
             serde_json::from_str(include_str!("vrmc_spring_bone.json"))?;
         success!()
     }
+
+    #[test]
+    fn deserialize_spring_joint_limit() -> TestResult {
+        let joint: SpringJoint = serde_json::from_str(
+            r#"{
+                "node": 0,
+                "extensions": {
+                    "BEVY_vrm1_springJointLimit": {
+                        "hinge": { "axis": [1, 0, 0], "min": -0.5, "max": 0.5 }
+                    }
+                }
+            }"#,
+        )?;
+        assert_eq!(
+            joint.extensions.and_then(|e| e.limit),
+            Some(SpringJointLimit::Hinge(HingeLimit {
+                axis: [1., 0., 0.],
+                min: -0.5,
+                max: 0.5,
+            }))
+        );
+        success!()
+    }
+
+    #[test]
+    fn clamp_cone_limit() {
+        let limit = SpringJointLimit::Cone(ConeLimit { angle: FRAC_PI_4 });
+        let dir = limit.clamp(Vec3::Y, Vec3::X);
+        assert!((Vec3::Y.angle_between(dir) - FRAC_PI_4).abs() < 1e-5);
+        assert!(dir.z.abs() < 1e-5);
+
+        let inside = Vec3::new(0.1, 1., 0.).normalize();
+        assert_eq!(limit.clamp(Vec3::Y, inside), inside);
+    }
+
+    #[test]
+    fn clamp_hinge_limit() {
+        let limit = SpringJointLimit::Hinge(HingeLimit {
+            axis: [1., 0., 0.],
+            min: -FRAC_PI_4,
+            max: FRAC_PI_4,
+        });
+        let dir = limit.clamp(Vec3::NEG_Y, Vec3::new(1., 0., 1.).normalize());
+        assert!(dir.x.abs() < 1e-5);
+        assert!((Vec3::NEG_Y.angle_between(dir) - FRAC_PI_4).abs() < 1e-5);
+    }
 }
@@ -1,6 +1,7 @@
 use bevy::color::LinearRgba;
+use bevy::math::{Affine2, Vec2};
+use bevy::pbr::UvChannel;
 use bevy::prelude::Reflect;
-use bevy::render::render_resource::ShaderType;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Reflect, Debug, Clone)]
@@ -86,57 +87,105 @@ impl VrmcMaterialsExtensitions {
     }
 }
 
+/// The reference to the texture in `VRMC_materials_mtoon`, which has the same shape as glTF `textureInfo`.
 #[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
-pub struct MatcapTexture {
+pub struct VrmTexture {
     pub index: usize,
+    /// The UV set used by the texture.
+    #[serde(rename = "texCoord", default)]
+    pub tex_coord: u32,
+    #[serde(default)]
+    pub extensions: VrmTextureExtensions,
 }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
-pub struct RimMultiplyTexture {
-    pub index: usize,
-}
+impl VrmTexture {
+    /// Returns the UV transform specified by `KHR_texture_transform`.
+    pub fn uv_transform(&self) -> Affine2 {
+        self.extensions
+            .khr_texture_transform
+            .as_ref()
+            .map(KhrTextureTransform::to_affine2)
+            .unwrap_or(Affine2::IDENTITY)
+    }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
-pub struct OutlineWidthMultiplyTexture {
-    pub index: usize,
+    /// Returns the UV set used by the texture.
+    ///
+    /// `KHR_texture_transform` can override `texCoord` of the texture.
+    pub fn uv_channel(&self) -> UvChannel {
+        let tex_coord = self
+            .extensions
+            .khr_texture_transform
+            .as_ref()
+            .and_then(|transform| transform.tex_coord)
+            .unwrap_or(self.tex_coord);
+        if tex_coord == 1 {
+            UvChannel::Uv1
+        } else {
+            UvChannel::Uv0
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
-pub struct UVAnimationMaskTexture {
-    pub index: usize,
-}
+pub type MatcapTexture = VrmTexture;
+pub type RimMultiplyTexture = VrmTexture;
+pub type OutlineWidthMultiplyTexture = VrmTexture;
+pub type UVAnimationMaskTexture = VrmTexture;
 
 #[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
 pub struct ShadingShiftTexture {
-    pub index: usize,
-    #[serde(rename = "texCoord")]
-    pub tex_coord: f32,
+    #[serde(flatten)]
+    pub texture: VrmTexture,
+    #[serde(default = "default_texture_scale")]
     pub scale: f32,
 }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
-pub struct VrmTexture {
-    pub extensions: VrmTextureExtensions,
-    pub index: usize,
+fn default_texture_scale() -> f32 {
+    1.0
 }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Reflect, Debug, Clone, Copy, Default)]
 pub struct VrmTextureExtensions {
-    #[serde(rename = "KHR_texture_transform")]
-    pub khr_texture_transform: KhrTextureTransform,
+    #[serde(rename = "KHR_texture_transform", default)]
+    pub khr_texture_transform: Option<KhrTextureTransform>,
 }
 
-#[derive(Serialize, Deserialize, Reflect, Debug, Clone, PartialEq, Copy, ShaderType)]
+/// [KHR_texture_transform](https://github.com/KhronosGroup/glTF/blob/main/extensions/2.0/Khronos/KHR_texture_transform/README.md)
+#[derive(Serialize, Deserialize, Reflect, Debug, Clone, PartialEq, Copy)]
 pub struct KhrTextureTransform {
+    #[serde(default)]
     pub offset: [f32; 2],
+    /// The rotation of the UV in radians, counter-clockwise around the origin.
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default = "default_texture_transform_scale")]
     pub scale: [f32; 2],
+    /// Overrides `texCoord` of the texture if specified.
+    #[serde(rename = "texCoord", default)]
+    pub tex_coord: Option<u32>,
+}
+
+impl KhrTextureTransform {
+    /// Converts into the UV transform in the same way as `bevy_gltf`.
+    pub fn to_affine2(&self) -> Affine2 {
+        Affine2::from_scale_angle_translation(
+            Vec2::from(self.scale),
+            -self.rotation,
+            Vec2::from(self.offset),
+        )
+    }
+}
+
+fn default_texture_transform_scale() -> [f32; 2] {
+    [1.0, 1.0]
 }
 
 impl Default for KhrTextureTransform {
     fn default() -> Self {
         Self {
             offset: [0.0, 0.0],
+            rotation: 0.0,
             scale: [1.0, 1.0],
+            tex_coord: None,
         }
     }
 }
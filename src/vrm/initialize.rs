@@ -71,6 +71,11 @@ fn spawn_vrm(
                     &node_assets,
                     &vrm.gltf.nodes,
                 ),
+                SpringJointLimitRegistry::new(
+                    &spring_bone.all_joints(),
+                    &node_assets,
+                    &vrm.gltf.nodes,
+                ),
                 SpringColliderRegistry::new(&spring_bone.colliders, &node_assets, &vrm.gltf.nodes),
                 SpringNodeRegistry::new(spring_bone, &node_assets, &vrm.gltf.nodes),
             ));
@@ -1,10 +1,14 @@
+mod lighting_settings;
 mod material;
+mod material_mode;
+mod outline_override;
 mod outline_pass;
 mod setup;
 
 use crate::error::vrm_error;
 use crate::prelude::*;
 use crate::vrm::gltf::materials::VrmcMaterialsExtensitions;
+use crate::vrm::mtoon::lighting_settings::MToonLightingSettingsPlugin;
 use crate::vrm::mtoon::outline_pass::MToonOutlinePlugin;
 use crate::vrm::mtoon::setup::MToonMaterialSetupPlugin;
 use bevy::asset::{AssetId, load_internal_asset, weak_handle};
@@ -12,13 +16,22 @@ use bevy::prelude::*;
 use std::collections::HashMap;
 
 pub mod prelude {
-    pub use crate::vrm::mtoon::{MtoonMaterialPlugin, VrmcMaterialRegistry, material::prelude::*};
+    pub use crate::vrm::mtoon::{
+        MtoonMaterialPlugin, VrmcMaterialRegistry,
+        lighting_settings::{MToonLightingSettings, MToonMainLight},
+        material::prelude::*,
+        material_mode::VrmMaterialMode,
+        outline_override::{MToonOutlineOverride, OutlineOverrideValue},
+        outline_pass::NoMToonOutline,
+    };
 }
 
 const MTOON_FRAGMENT_SHADER_HANDLE: Handle<Shader> =
     weak_handle!("9a96eff2-1676-1dc0-9abc-2fd5e7134443");
 const MTOON_VERTEX_SHADER_HANDLE: Handle<Shader> =
     weak_handle!("f4041db8-c464-b84c-e3c9-e618527945a1");
+const MTOON_PREPASS_SHADER_HANDLE: Handle<Shader> =
+    weak_handle!("c2b7e4d1-8f3a-4e6b-9d05-7a1c3e9f2b84");
 const MTOON_TYPES_SHADER_HANDLE: Handle<Shader> =
     weak_handle!("5d9302a3-6498-9d2a-fadb-842d01c87697");
 
@@ -35,8 +48,15 @@ impl Plugin for MtoonMaterialPlugin {
             .register_type::<RimLighting>()
             .register_type::<UVAnimation>()
             .register_type::<Shade>()
+            .register_type::<VrmMaterialMode>()
+            .register_type::<NoMToonOutline>()
+            .register_type::<MToonOutlineOverride>()
             .add_plugins(MaterialPlugin::<MToonMaterial>::default())
-            .add_plugins((MToonMaterialSetupPlugin, MToonOutlinePlugin));
+            .add_plugins((
+                MToonMaterialSetupPlugin,
+                MToonOutlinePlugin,
+                MToonLightingSettingsPlugin,
+            ));
         load_internal_asset!(
             app,
             MTOON_FRAGMENT_SHADER_HANDLE,
@@ -55,6 +75,12 @@ impl Plugin for MtoonMaterialPlugin {
             "mtoon_vertex.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            MTOON_PREPASS_SHADER_HANDLE,
+            "mtoon_prepass.wgsl",
+            Shader::from_wgsl
+        );
     }
 }
 
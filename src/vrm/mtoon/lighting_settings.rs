@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Buffer, ShaderType, UniformBuffer};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
+
+const MAIN_LIGHT_OVERRIDE: u32 = 1 << 0;
+const TOONY_FACTOR_OVERRIDE: u32 = 1 << 1;
+
+/// The binding index of [`MToonLightingSettings`] in the bind group of [`MToonMaterial`](crate::prelude::MToonMaterial).
+pub(super) const LIGHTING_SETTINGS_BINDING: u32 = 117;
+
+pub(super) struct MToonLightingSettingsPlugin;
+
+impl Plugin for MToonLightingSettingsPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<MToonLightingSettings>()
+            .register_type::<MToonMainLight>()
+            .init_resource::<MToonLightingSettings>();
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<ExtractedLightingSettings>()
+            .add_systems(ExtractSchedule, extract_lighting_settings)
+            .add_systems(
+                Render,
+                prepare_lighting_settings_buffer.in_set(RenderSet::PrepareResources),
+            );
+    }
+
+    fn finish(
+        &self,
+        app: &mut App,
+    ) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<MToonLightingSettingsBuffer>();
+    }
+}
+
+/// Overrides the lighting of all [`MToonMaterial`](crate::prelude::MToonMaterial)s.
+///
+/// This is useful for stage scenes that want every avatar to be lit in the same way regardless of the scene lights.
+/// This resource is bound to the shader separately from the materials, so the material assets are not modified.
+/// The default value leaves the lighting as it is.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn setup_stage_lighting(mut commands: Commands) {
+///     commands.insert_resource(MToonLightingSettings {
+///         main_light: Some(MToonMainLight {
+///             direction: Dir3::new(Vec3::new(0.3, -0.5, -1.)).unwrap(),
+///             color: LinearRgba::WHITE,
+///         }),
+///         ambient: LinearRgba::rgb(0.1, 0.1, 0.15),
+///         ..default()
+///     });
+/// }
+/// ```
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct MToonLightingSettings {
+    /// If set, the materials are shaded by this light instead of the scene lights.
+    ///
+    /// The shadows of the scene lights are not applied while this is set.
+    pub main_light: Option<MToonMainLight>,
+    /// The maximum value of each color channel of the direct and indirect lighting.
+    ///
+    /// The emission is not clamped.
+    pub max_intensity: f32,
+    /// The ambient light added to the indirect lighting.
+    ///
+    /// Unlike [`AmbientLight`], this is not scaled by the exposure of the camera.
+    pub ambient: LinearRgba,
+    /// If set, overrides [`Shade::toony_factor`](crate::prelude::Shade::toony_factor) of all materials.
+    pub shading_toony_factor: Option<f32>,
+}
+
+impl Default for MToonLightingSettings {
+    fn default() -> Self {
+        Self {
+            main_light: None,
+            max_intensity: f32::MAX,
+            ambient: LinearRgba::BLACK,
+            shading_toony_factor: None,
+        }
+    }
+}
+
+/// The light that replaces the scene lights in [`MToonLightingSettings`].
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MToonMainLight {
+    /// The direction in which the light travels.
+    pub direction: Dir3,
+    /// The color multiplied to the direct lighting.
+    pub color: LinearRgba,
+}
+
+/// The settings in the layout of `MToonLightingSettings` in `mtoon_types.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, ShaderType)]
+pub(super) struct MToonLightingSettingsUniform {
+    flags: u32,
+    main_light_direction: Vec3,
+    main_light_color: Vec3,
+    shading_toony_factor: f32,
+    ambient: Vec3,
+    max_intensity: f32,
+}
+
+impl From<&MToonLightingSettings> for MToonLightingSettingsUniform {
+    fn from(settings: &MToonLightingSettings) -> Self {
+        let mut flags = 0;
+        let (main_light_direction, main_light_color) = match settings.main_light {
+            Some(light) => {
+                flags |= MAIN_LIGHT_OVERRIDE;
+                (-light.direction.as_vec3(), light.color.to_vec3())
+            }
+            None => (Vec3::ZERO, Vec3::ONE),
+        };
+        if settings.shading_toony_factor.is_some() {
+            flags |= TOONY_FACTOR_OVERRIDE;
+        }
+        Self {
+            flags,
+            main_light_direction,
+            main_light_color,
+            shading_toony_factor: settings.shading_toony_factor.unwrap_or_default(),
+            ambient: settings.ambient.to_vec3(),
+            max_intensity: settings.max_intensity,
+        }
+    }
+}
+
+/// [`MToonLightingSettings`] extracted from the main world.
+#[derive(Resource, Deref)]
+pub(super) struct ExtractedLightingSettings(MToonLightingSettingsUniform);
+
+impl Default for ExtractedLightingSettings {
+    fn default() -> Self {
+        Self(MToonLightingSettingsUniform::from(
+            &MToonLightingSettings::default(),
+        ))
+    }
+}
+
+/// The uniform buffer of [`MToonLightingSettings`] in the render world.
+///
+/// The same buffer is bound to the bind groups of all materials,
+/// so the prepared materials see the changes of the settings without being rebuilt.
+#[derive(Resource)]
+pub struct MToonLightingSettingsBuffer(UniformBuffer<MToonLightingSettingsUniform>);
+
+impl MToonLightingSettingsBuffer {
+    #[inline]
+    pub(super) fn buffer(&self) -> Option<&Buffer> {
+        self.0.buffer()
+    }
+}
+
+impl FromWorld for MToonLightingSettingsBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let mut uniform = UniformBuffer::from(*ExtractedLightingSettings::default());
+        // The buffer is created here, so that it exists before the first material is prepared.
+        uniform.write_buffer(
+            world.resource::<RenderDevice>(),
+            world.resource::<RenderQueue>(),
+        );
+        Self(uniform)
+    }
+}
+
+fn extract_lighting_settings(
+    mut extracted: ResMut<ExtractedLightingSettings>,
+    settings: Extract<Option<Res<MToonLightingSettings>>>,
+) {
+    let settings = settings.as_deref().copied().unwrap_or_default();
+    extracted.0 = MToonLightingSettingsUniform::from(&settings);
+}
+
+fn prepare_lighting_settings_buffer(
+    mut buffer: ResMut<MToonLightingSettingsBuffer>,
+    extracted: Res<ExtractedLightingSettings>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    // The buffer is reused while its size is unchanged, so the bind groups keep referring to it.
+    buffer.0.set(**extracted);
+    buffer.0.write_buffer(&render_device, &render_queue);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestExtract, test_app};
+    use crate::vrm::mtoon::lighting_settings::{
+        ExtractedLightingSettings, MToonLightingSettingsPlugin, MToonLightingSettingsUniform,
+        extract_lighting_settings,
+    };
+    use bevy::prelude::*;
+
+    #[test]
+    fn extract_settings_changed_after_material_is_prepared() {
+        let mut app = test_app();
+        app.add_plugins(MToonLightingSettingsPlugin);
+        app.init_asset::<MToonMaterial>();
+        app.world_mut()
+            .resource_mut::<Assets<MToonMaterial>>()
+            .add(MToonMaterial::default());
+        app.update();
+
+        let mut extract = TestExtract::new(extract_lighting_settings);
+        extract
+            .render_world
+            .init_resource::<ExtractedLightingSettings>();
+        extract.run(&mut app);
+        let default_settings = **extract.render_world.resource::<ExtractedLightingSettings>();
+
+        // The settings are changed without touching the material that has already been prepared.
+        app.world_mut()
+            .resource_mut::<MToonLightingSettings>()
+            .shading_toony_factor = Some(0.5);
+        app.update();
+        extract.run(&mut app);
+        let settings = MToonLightingSettings {
+            shading_toony_factor: Some(0.5),
+            ..default()
+        };
+        assert_eq!(
+            **extract.render_world.resource::<ExtractedLightingSettings>(),
+            MToonLightingSettingsUniform::from(&settings)
+        );
+
+        app.world_mut().remove_resource::<MToonLightingSettings>();
+        app.update();
+        extract.run(&mut app);
+        assert_eq!(
+            **extract.render_world.resource::<ExtractedLightingSettings>(),
+            default_settings
+        );
+    }
+}
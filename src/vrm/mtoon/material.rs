@@ -1,23 +1,28 @@
+mod bind_group;
 mod outline;
 mod rim_lighting;
 mod shade;
+mod texture_transform;
 mod uv_animation;
 
 use crate::vrm::mtoon::material::outline::{MToonOutline, OutlineWidthMode};
-use crate::vrm::mtoon::{MTOON_FRAGMENT_SHADER_HANDLE, MTOON_VERTEX_SHADER_HANDLE};
+use crate::vrm::mtoon::{
+    MTOON_FRAGMENT_SHADER_HANDLE, MTOON_PREPASS_SHADER_HANDLE, MTOON_VERTEX_SHADER_HANDLE,
+};
 use bevy::math::Affine2;
-use bevy::pbr::{MaterialPipeline, MaterialPipelineKey, OpaqueRendererMethod};
+use bevy::pbr::{MaterialPipeline, MaterialPipelineKey, OpaqueRendererMethod, UvChannel};
 use bevy::prelude::*;
 use bevy::render::mesh::MeshVertexBufferLayoutRef;
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_resource::{
-    AsBindGroup, AsBindGroupShaderType, Face, RenderPipelineDescriptor, ShaderRef, ShaderType,
+    AsBindGroupShaderType, Face, RenderPipelineDescriptor, ShaderRef, ShaderType,
     SpecializedMeshPipelineError,
 };
 use bevy::render::texture::GpuImage;
 use bitflags::bitflags;
 pub use rim_lighting::RimLighting;
 pub use shade::Shade;
+use texture_transform::MToonTextureTransforms;
 pub use uv_animation::UVAnimation;
 
 pub mod prelude {
@@ -26,57 +31,47 @@ pub mod prelude {
         outline::{MToonOutline, OutlineWidthMode},
         rim_lighting::RimLighting,
         shade::Shade,
+        texture_transform::{MToonTextureTransform, MToonTextureTransforms},
         uv_animation::UVAnimation,
     };
 }
 
 /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md)
-#[derive(Asset, AsBindGroup, PartialEq, Debug, Clone, Component, Reflect)]
+#[derive(Asset, PartialEq, Debug, Clone, Component, Reflect)]
 #[reflect(Component)]
-#[data(100, MToonMaterialUniform)]
-#[bind_group_data(MToonMaterialKey)]
 pub struct MToonMaterial {
     /// The texture that defines the base color of the material.
-    #[texture(101)]
-    #[sampler(102)]
     #[dependency]
     pub base_color_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#shadingshifttexture)
-    #[texture(103)]
-    #[sampler(104)]
     #[dependency]
     pub shading_shift_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#shademultiplytexture)
-    #[texture(105)]
-    #[sampler(106)]
     #[dependency]
     pub shade_multiply_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#rim-multiply-texture)
-    #[texture(107)]
-    #[sampler(108)]
     #[dependency]
     pub rim_multiply_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#uv-animation-mask-texture)
-    #[texture(109)]
-    #[sampler(110)]
     #[dependency]
     pub uv_animation_mask_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#matcaptexture)
-    #[texture(111)]
-    #[sampler(112)]
     #[dependency]
     pub matcap_texture: Option<Handle<Image>>,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#emission)
-    #[texture(113)]
-    #[sampler(114)]
     #[dependency]
     pub emissive_texture: Option<Handle<Image>>,
-    #[texture(115)]
-    #[sampler(116)]
     #[dependency]
     pub outline_width_multiply_texture: Option<Handle<Image>>,
     pub uv_animation: UVAnimation,
+    /// The UV transform of the base color and emissive textures.
     pub uv_transform: Affine2,
+    /// The UV set used by the base color texture.
+    pub base_color_channel: UvChannel,
+    /// The UV set used by the emissive texture.
+    pub emissive_channel: UvChannel,
+    /// The UV transforms and UV sets of the other textures.
+    pub texture_transforms: MToonTextureTransforms,
     pub rim_lighting: RimLighting,
     pub shade: Shade,
     pub outline: MToonOutline,
@@ -84,14 +79,31 @@ pub struct MToonMaterial {
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#emission)
     pub emissive: LinearRgba,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#giequalizationfactor)
+    ///
+    /// This flattens the irradiance of [`EnvironmentMapLight`](bevy::pbr::environment_map::EnvironmentMapLight) and [`IrradianceVolume`](bevy::pbr::irradiance_volume::IrradianceVolume);
+    /// `1.0` ignores the normal direction, and `0.0` uses the irradiance in the normal direction as it is.
     pub gi_equalization_factor: f32,
     pub alpha_mode: AlphaMode,
     pub double_sided: bool,
     /// [VRMC_materials_mtoon-1.0](https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#renderqueueoffsetnumber)
     pub depth_bias: f32,
+    #[deprecated(note = "MToon is always rendered in the forward pass, so this is ignored.")]
     pub opaque_renderer_method: OpaqueRendererMethod,
     pub render_queue_offset: f32,
     pub transparent_with_z_write: bool,
+    /// Whether this material receives shadows from the shadow-casting lights.
+    ///
+    /// Disabling this is useful for the face materials,
+    /// which are prone to shadow acne caused by self-shadowing.
+    ///
+    /// VRM doesn't declare this, so it is always `true` when loaded.
+    /// Edit it per material with [`MToonMaterials`](crate::prelude::MToonMaterials) after the VRM is spawned,
+    /// or insert [`NotShadowReceiver`](bevy::pbr::NotShadowReceiver) into the mesh entities.
+    pub receive_shadows: bool,
+    /// Whether to render with the reduced shader.
+    ///
+    /// See [`VrmMaterialMode::MToonLite`](crate::prelude::VrmMaterialMode::MToonLite) for details.
+    pub lite: bool,
     #[reflect(ignore, clone)]
     pub cull_mode: Option<Face>,
 }
@@ -103,6 +115,9 @@ bitflags! {
         const CULL_FRONT = 1 << 0;
         const CULL_BACK = 1 << 1;
         const TRANSPARENT_WITH_Z_WRITE = 1 << 2;
+        const OUTLINE_SCREEN_COORDINATES = 1 << 3;
+        const LITE = 1 << 4;
+        const OUTLINE = 1 << 5;
     }
 }
 
@@ -115,12 +130,18 @@ impl Material for MToonMaterial {
         MTOON_FRAGMENT_SHADER_HANDLE.into()
     }
 
+    fn prepass_fragment_shader() -> ShaderRef {
+        MTOON_PREPASS_SHADER_HANDLE.into()
+    }
+
     fn alpha_mode(&self) -> AlphaMode {
         self.alpha_mode
     }
 
+    /// This material is always rendered in the forward pass,
+    /// since the toon shading can't be resolved by the lighting pass of the deferred pipeline.
     fn opaque_render_method(&self) -> OpaqueRendererMethod {
-        self.opaque_renderer_method
+        OpaqueRendererMethod::Forward
     }
 
     fn depth_bias(&self) -> f32 {
@@ -162,6 +183,20 @@ impl Material for MToonMaterial {
                 stencil.depth_write_enabled = true;
             }
         }
+        if key
+            .bind_group_data
+            .contains(MToonMaterialKey::OUTLINE_SCREEN_COORDINATES)
+        {
+            descriptor
+                .vertex
+                .shader_defs
+                .push("OUTLINE_SCREEN_COORDINATES".into());
+        }
+        if key.bind_group_data.contains(MToonMaterialKey::LITE)
+            && let Some(fragment) = descriptor.fragment.as_mut()
+        {
+            fragment.shader_defs.push("MTOON_LITE".into());
+        }
         Ok(())
     }
 }
@@ -181,11 +216,21 @@ impl From<&MToonMaterial> for MToonMaterialKey {
             MToonMaterialKey::TRANSPARENT_WITH_Z_WRITE,
             matches!(material.alpha_mode, AlphaMode::Blend) && material.transparent_with_z_write,
         );
+        key.set(
+            MToonMaterialKey::OUTLINE_SCREEN_COORDINATES,
+            matches!(material.outline.mode, OutlineWidthMode::ScreenCoordinates),
+        );
+        key.set(MToonMaterialKey::LITE, material.lite);
+        key.set(
+            MToonMaterialKey::OUTLINE,
+            !material.lite && material.outline.mode != OutlineWidthMode::None,
+        );
         key
     }
 }
 
 impl Default for MToonMaterial {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             base_color_texture: None,
@@ -198,6 +243,9 @@ impl Default for MToonMaterial {
             emissive_texture: None,
             uv_animation: UVAnimation::default(),
             uv_transform: Affine2::IDENTITY,
+            base_color_channel: UvChannel::Uv0,
+            emissive_channel: UvChannel::Uv0,
+            texture_transforms: MToonTextureTransforms::default(),
             rim_lighting: RimLighting::default(),
             shade: Shade::default(),
             base_color: Color::WHITE,
@@ -208,6 +256,8 @@ impl Default for MToonMaterial {
             depth_bias: 0.0,
             render_queue_offset: 0.0,
             transparent_with_z_write: false,
+            receive_shadows: true,
+            lite: false,
             opaque_renderer_method: OpaqueRendererMethod::default(),
             cull_mode: None,
             outline: MToonOutline::default(),
@@ -230,6 +280,7 @@ bitflags::bitflags! {
         const ALPHA_MODE_ALPHA_TO_COVERAGE = 1 << 9;
         const ALPHA_MODE_BLEND = 1 << 10;
         const OUTLINE_WIDTH_MULTIPLY_TEXTURE = 1 << 11;
+        const RECEIVE_SHADOWS = 1 << 12;
     }
 }
 
@@ -274,6 +325,7 @@ impl From<&MToonMaterial> for MtoonFlags {
             MtoonFlags::OUTLINE_WIDTH_MULTIPLY_TEXTURE,
             value.outline_width_multiply_texture.is_some(),
         );
+        flags.set(MtoonFlags::RECEIVE_SHADOWS, value.receive_shadows);
         flags
     }
 }
@@ -282,6 +334,7 @@ bitflags::bitflags! {
     #[repr(transparent)]
     pub struct OutlineWidthModeFlags: u32 {
         const WORLD_COORDINATES = 1 << 0;
+        const SCREEN_COORDINATES = 1 << 1;
     }
 }
 
@@ -293,7 +346,6 @@ pub struct MToonMaterialUniform {
     pub shade_color: Vec4,
     pub emissive_color: Vec4,
     pub shading_shift_factor: f32,
-    pub shading_shift_texture_offset: f32,
     pub shading_shift_texture_scale: f32,
     pub shading_shift_toony_factor: f32,
     pub gi_equalization_factor: f32,
@@ -311,6 +363,14 @@ pub struct MToonMaterialUniform {
     pub outline_color: Vec4,
     pub outline_width_factor: f32,
     pub outline_lighting_mix_factor: f32,
+    pub shading_shift_uv_transform: Mat3,
+    pub shade_multiply_uv_transform: Mat3,
+    pub rim_multiply_uv_transform: Mat3,
+    pub uv_animation_mask_uv_transform: Mat3,
+    pub matcap_uv_transform: Mat3,
+    pub outline_width_multiply_uv_transform: Mat3,
+    /// The textures that use the second UV set, with the same bits as [`MtoonFlags`].
+    pub uv_channels: u32,
 }
 
 impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
@@ -323,11 +383,42 @@ impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
             OutlineWidthModeFlags::WORLD_COORDINATES,
             matches!(self.outline.mode, OutlineWidthMode::WorldCoordinates),
         );
+        outline_flags.set(
+            OutlineWidthModeFlags::SCREEN_COORDINATES,
+            matches!(self.outline.mode, OutlineWidthMode::ScreenCoordinates),
+        );
+        let transforms = &self.texture_transforms;
+        let mut uv_channels = MtoonFlags::empty();
+        for (flag, channel) in [
+            (MtoonFlags::BASE_COLOR_TEXTURE, &self.base_color_channel),
+            (MtoonFlags::EMISSIVE_TEXTURE, &self.emissive_channel),
+            (
+                MtoonFlags::SHADING_SHIFT_TEXTURE,
+                &transforms.shading_shift.channel,
+            ),
+            (
+                MtoonFlags::SHADE_MULTIPLY_TEXTURE,
+                &transforms.shade_multiply.channel,
+            ),
+            (
+                MtoonFlags::RIM_MAP_TEXTURE,
+                &transforms.rim_multiply.channel,
+            ),
+            (
+                MtoonFlags::UV_ANIMATION_MASK_TEXTURE,
+                &transforms.uv_animation_mask.channel,
+            ),
+            (
+                MtoonFlags::OUTLINE_WIDTH_MULTIPLY_TEXTURE,
+                &transforms.outline_width_multiply.channel,
+            ),
+        ] {
+            uv_channels.set(flag, *channel == UvChannel::Uv1);
+        }
         MToonMaterialUniform {
             flags: MtoonFlags::from(self).bits(),
             shade_color: self.shade.color.to_vec4(),
             shading_shift_factor: self.shade.shading_shift_factor,
-            shading_shift_texture_offset: self.shade.texture_offset,
             shading_shift_texture_scale: self.shade.texture_scale,
             shading_shift_toony_factor: self.shade.toony_factor,
             gi_equalization_factor: self.gi_equalization_factor,
@@ -350,6 +441,13 @@ impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
             outline_color: self.outline.color.to_vec4(),
             outline_width_factor: self.outline.width_factor,
             outline_lighting_mix_factor: self.outline.lighting_mix_factor,
+            shading_shift_uv_transform: transforms.shading_shift.transform.into(),
+            shade_multiply_uv_transform: transforms.shade_multiply.transform.into(),
+            rim_multiply_uv_transform: transforms.rim_multiply.transform.into(),
+            uv_animation_mask_uv_transform: transforms.uv_animation_mask.transform.into(),
+            matcap_uv_transform: transforms.matcap.transform.into(),
+            outline_width_multiply_uv_transform: transforms.outline_width_multiply.transform.into(),
+            uv_channels: uv_channels.bits(),
         }
     }
 }
@@ -0,0 +1,128 @@
+use crate::vrm::mtoon::lighting_settings::{
+    LIGHTING_SETTINGS_BINDING, MToonLightingSettingsBuffer, MToonLightingSettingsUniform,
+};
+use crate::vrm::mtoon::material::{MToonMaterial, MToonMaterialKey, MToonMaterialUniform};
+use bevy::ecs::system::SystemParamItem;
+use bevy::ecs::system::lifetimeless::SRes;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::binding_types::uniform_buffer;
+use bevy::render::render_resource::{
+    AsBindGroup, AsBindGroupError, AsBindGroupShaderType, BindGroupLayout, BindGroupLayoutEntry,
+    OwnedBindingResource, ShaderStages, UnpreparedBindGroup,
+};
+use bevy::render::renderer::RenderDevice;
+use bevy::render::storage::GpuShaderStorageBuffer;
+use bevy::render::texture::{FallbackImage, GpuImage};
+
+/// The bindings of [`MToonMaterial`] except for [`MToonLightingSettings`](crate::prelude::MToonLightingSettings).
+#[derive(AsBindGroup)]
+#[data(100, MToonMaterialUniform)]
+struct MToonMaterialBindings {
+    uniform: MToonMaterialUniform,
+    #[texture(101)]
+    #[sampler(102)]
+    base_color_texture: Option<Handle<Image>>,
+    #[texture(103)]
+    #[sampler(104)]
+    shading_shift_texture: Option<Handle<Image>>,
+    #[texture(105)]
+    #[sampler(106)]
+    shade_multiply_texture: Option<Handle<Image>>,
+    #[texture(107)]
+    #[sampler(108)]
+    rim_multiply_texture: Option<Handle<Image>>,
+    #[texture(109)]
+    #[sampler(110)]
+    uv_animation_mask_texture: Option<Handle<Image>>,
+    #[texture(111)]
+    #[sampler(112)]
+    matcap_texture: Option<Handle<Image>>,
+    #[texture(113)]
+    #[sampler(114)]
+    emissive_texture: Option<Handle<Image>>,
+    #[texture(115)]
+    #[sampler(116)]
+    outline_width_multiply_texture: Option<Handle<Image>>,
+}
+
+impl From<&MToonMaterialBindings> for MToonMaterialUniform {
+    fn from(bindings: &MToonMaterialBindings) -> Self {
+        bindings.uniform.clone()
+    }
+}
+
+/// The lighting settings are shared by all materials,
+/// so they are bound from the render world instead of a field of the material.
+impl AsBindGroup for MToonMaterial {
+    type Data = MToonMaterialKey;
+
+    type Param = (
+        SRes<RenderAssets<GpuImage>>,
+        SRes<FallbackImage>,
+        SRes<RenderAssets<GpuShaderStorageBuffer>>,
+        SRes<MToonLightingSettingsBuffer>,
+    );
+
+    fn label() -> Option<&'static str> {
+        Some("mtoon_material")
+    }
+
+    fn unprepared_bind_group(
+        &self,
+        layout: &BindGroupLayout,
+        render_device: &RenderDevice,
+        (images, fallback_image, storage_buffers, lighting_settings): &mut SystemParamItem<
+            '_,
+            '_,
+            Self::Param,
+        >,
+        force_no_bindless: bool,
+    ) -> Result<UnpreparedBindGroup<Self::Data>, AsBindGroupError> {
+        let Some(lighting_settings) = lighting_settings.buffer() else {
+            return Err(AsBindGroupError::RetryNextUpdate);
+        };
+        let bindings = MToonMaterialBindings {
+            uniform: self.as_bind_group_shader_type(images),
+            base_color_texture: self.base_color_texture.clone(),
+            shading_shift_texture: self.shading_shift_texture.clone(),
+            shade_multiply_texture: self.shade_multiply_texture.clone(),
+            rim_multiply_texture: self.rim_multiply_texture.clone(),
+            uv_animation_mask_texture: self.uv_animation_mask_texture.clone(),
+            matcap_texture: self.matcap_texture.clone(),
+            emissive_texture: self.emissive_texture.clone(),
+            outline_width_multiply_texture: self.outline_width_multiply_texture.clone(),
+        };
+        let mut bind_group = bindings.unprepared_bind_group(
+            layout,
+            render_device,
+            &mut (
+                Res::clone(images),
+                Res::clone(fallback_image),
+                Res::clone(storage_buffers),
+            ),
+            force_no_bindless,
+        )?;
+        bind_group.bindings.push((
+            LIGHTING_SETTINGS_BINDING,
+            OwnedBindingResource::Buffer(lighting_settings.clone()),
+        ));
+        Ok(UnpreparedBindGroup {
+            bindings: bind_group.bindings,
+            data: MToonMaterialKey::from(self),
+        })
+    }
+
+    fn bind_group_layout_entries(
+        render_device: &RenderDevice,
+        force_no_bindless: bool,
+    ) -> Vec<BindGroupLayoutEntry> {
+        let mut entries =
+            MToonMaterialBindings::bind_group_layout_entries(render_device, force_no_bindless);
+        entries.push(
+            uniform_buffer::<MToonLightingSettingsUniform>(false)
+                .build(LIGHTING_SETTINGS_BINDING, ShaderStages::FRAGMENT),
+        );
+        entries
+    }
+}
@@ -10,8 +10,6 @@ pub struct Shade {
     pub color: LinearRgba,
     /// The value to shift the shading boundary.
     pub shading_shift_factor: f32,
-    /// The offset of the shading shift texture.
-    pub texture_offset: f32,
     /// The scale of the shading shift texture.
     pub texture_scale: f32,
     /// The value to specify the smoothness of the shading boundary.
@@ -23,11 +21,6 @@ impl From<&VrmcMaterialsExtensitions> for Shade {
         Self {
             color: extension.shade_color(),
             shading_shift_factor: extension.shading_shift_factor,
-            texture_offset: extension
-                .shading_shift_texture
-                .as_ref()
-                .map(|t| t.tex_coord)
-                .unwrap_or(1.),
             texture_scale: extension
                 .shading_shift_texture
                 .as_ref()
@@ -43,7 +36,6 @@ impl Default for Shade {
         Self {
             color: LinearRgba::BLACK,
             shading_shift_factor: 0.0,
-            texture_offset: 0.0,
             texture_scale: 1.0,
             toony_factor: 0.9,
         }
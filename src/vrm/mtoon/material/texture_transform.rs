@@ -0,0 +1,101 @@
+use crate::vrm::gltf::materials::{VrmTexture, VrmcMaterialsExtensitions};
+use bevy::math::Affine2;
+use bevy::pbr::UvChannel;
+use bevy::prelude::*;
+
+/// The UV transform and the UV set of a texture.
+///
+/// These are obtained from `KHR_texture_transform` and `texCoord` of each texture.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[reflect(Default)]
+pub struct MToonTextureTransform {
+    /// The UV transform applied before sampling the texture.
+    pub transform: Affine2,
+    /// The UV set used to sample the texture.
+    pub channel: UvChannel,
+}
+
+impl Default for MToonTextureTransform {
+    fn default() -> Self {
+        Self {
+            transform: Affine2::IDENTITY,
+            channel: UvChannel::Uv0,
+        }
+    }
+}
+
+impl From<&VrmTexture> for MToonTextureTransform {
+    fn from(texture: &VrmTexture) -> Self {
+        Self {
+            transform: texture.uv_transform(),
+            channel: texture.uv_channel(),
+        }
+    }
+}
+
+/// The [`MToonTextureTransform`]s of the textures defined in `VRMC_materials_mtoon`.
+///
+/// The base color and emissive textures use [`MToonMaterial::uv_transform`](crate::prelude::MToonMaterial::uv_transform)
+/// like [`StandardMaterial`].
+#[derive(Debug, Clone, Default, PartialEq, Reflect)]
+#[reflect(Default)]
+pub struct MToonTextureTransforms {
+    pub shading_shift: MToonTextureTransform,
+    pub shade_multiply: MToonTextureTransform,
+    pub rim_multiply: MToonTextureTransform,
+    pub uv_animation_mask: MToonTextureTransform,
+    pub matcap: MToonTextureTransform,
+    pub outline_width_multiply: MToonTextureTransform,
+}
+
+impl From<&VrmcMaterialsExtensitions> for MToonTextureTransforms {
+    fn from(extension: &VrmcMaterialsExtensitions) -> Self {
+        let transform = |texture: Option<&VrmTexture>| {
+            texture.map(MToonTextureTransform::from).unwrap_or_default()
+        };
+        Self {
+            shading_shift: transform(
+                extension
+                    .shading_shift_texture
+                    .as_ref()
+                    .map(|texture| &texture.texture),
+            ),
+            shade_multiply: transform(extension.shade_multiply_texture.as_ref()),
+            rim_multiply: transform(extension.rim_multiply_texture.as_ref()),
+            uv_animation_mask: transform(extension.uv_animation_mask_texture.as_ref()),
+            matcap: transform(extension.matcap_texture.as_ref()),
+            outline_width_multiply: transform(extension.outline_width_multiply_texture.as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use bevy::math::{Affine2, Vec2};
+    use bevy::pbr::UvChannel;
+
+    #[test]
+    fn parse_khr_texture_transform() {
+        let texture: VrmTexture = serde_json::from_str(
+            r#"{
+                "index": 0,
+                "texCoord": 0,
+                "extensions": {
+                    "KHR_texture_transform": {
+                        "offset": [0.5, 0.0],
+                        "scale": [2.0, 2.0],
+                        "texCoord": 1
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let transform = MToonTextureTransform::from(&texture);
+        assert_eq!(transform.channel, UvChannel::Uv1);
+        assert_eq!(
+            transform.transform,
+            Affine2::from_scale_angle_translation(Vec2::splat(2.), 0., Vec2::new(0.5, 0.))
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+
+/// Selects how the materials of VRM are rendered.
+///
+/// Insert this as a resource to change the mode of all VRMs,
+/// or insert it into the entity of VRM to override the mode per avatar.
+///
+/// The mode is applied when the materials of VRM are loaded,
+/// so changing it afterwards doesn't affect the VRMs already spawned; respawn them to apply the new mode.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn spawn_vrm(
+///     mut commands: Commands,
+///     asset_server: Res<AssetServer>,
+/// ){
+///     commands.spawn((
+///         VrmHandle(asset_server.load("<vrm>.vrm")),
+///         VrmMaterialMode::MToonLite,
+///     ));
+/// }
+/// ```
+#[derive(Component, Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component, Resource, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub enum VrmMaterialMode {
+    /// Renders the materials with the full [`MToonMaterial`](crate::prelude::MToonMaterial), including outlines.
+    #[default]
+    MToon,
+    /// Renders the materials with a reduced version of the toon shader.
+    ///
+    /// It only uses directional lights without shadows,
+    /// and skips the light probes, the rim lighting, the UV animation and the outline.
+    MToonLite,
+    /// Keeps the original [`StandardMaterial`] loaded from glTF.
+    ///
+    /// This is useful for the platforms which can't afford the toon shader, such as `WebGL2` or deferred-only pipelines.
+    Standard,
+}
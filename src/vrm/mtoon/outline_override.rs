@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+/// Overrides the outlines of all meshes of the VRM at render time.
+///
+/// This component should be inserted into the root entity of the VRM.
+/// Unlike editing [`MToonOutline`](crate::prelude::MToonOutline), the material assets are not modified,
+/// so this is suitable for temporary effects such as highlighting the selected character.
+///
+/// To hide the outlines for specific cameras, insert [`NoMToonOutline`](crate::prelude::NoMToonOutline) into the cameras.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn highlight(
+///     In(vrm): In<Entity>,
+///     mut commands: Commands,
+/// ) {
+///     commands.entity(vrm).insert(MToonOutlineOverride {
+///         width: OutlineOverrideValue::Multiply(2.),
+///         color: OutlineOverrideValue::Override(LinearRgba::rgb(1., 0.8, 0.)),
+///         ..default()
+///     });
+/// }
+/// ```
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct MToonOutlineOverride {
+    /// The outline width.
+    pub width: OutlineOverrideValue<f32>,
+    /// The outline color.
+    pub color: OutlineOverrideValue<LinearRgba>,
+    /// The factor for the outline lighting mix.
+    pub lighting_mix_factor: OutlineOverrideValue<f32>,
+    /// If `false`, the outlines of the VRM are not drawn.
+    pub visible: bool,
+}
+
+impl MToonOutlineOverride {
+    /// Creates a new [`MToonOutlineOverride`] that hides the outlines.
+    pub fn hidden() -> Self {
+        Self {
+            visible: false,
+            ..default()
+        }
+    }
+}
+
+impl Default for MToonOutlineOverride {
+    fn default() -> Self {
+        Self {
+            width: OutlineOverrideValue::Multiply(1.),
+            color: OutlineOverrideValue::Multiply(LinearRgba::WHITE),
+            lighting_mix_factor: OutlineOverrideValue::Multiply(1.),
+            visible: true,
+        }
+    }
+}
+
+/// How [`MToonOutlineOverride`] applies a value to the one of the material.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutlineOverrideValue<T> {
+    /// Multiplies the value of the material.
+    Multiply(T),
+    /// Replaces the value of the material.
+    Override(T),
+}
+
+impl OutlineOverrideValue<f32> {
+    /// Returns the scale and the offset applied as `value * scale + offset` in the shader.
+    pub(crate) fn scale_offset(&self) -> Vec2 {
+        match self {
+            Self::Multiply(value) => Vec2::new(*value, 0.),
+            Self::Override(value) => Vec2::new(0., *value),
+        }
+    }
+}
+
+impl OutlineOverrideValue<LinearRgba> {
+    /// Returns the scale and the offset applied as `value * scale + offset` in the shader.
+    pub(crate) fn scale_offset(&self) -> (Vec4, Vec4) {
+        match self {
+            Self::Multiply(color) => (color.to_vec4(), Vec4::ZERO),
+            Self::Override(color) => (Vec4::ZERO, color.to_vec4()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use bevy::prelude::*;
+
+    #[test]
+    fn scale_offset() {
+        assert_eq!(
+            OutlineOverrideValue::Multiply(2.).scale_offset(),
+            Vec2::new(2., 0.)
+        );
+        assert_eq!(
+            OutlineOverrideValue::Override(0.5).scale_offset(),
+            Vec2::new(0., 0.5)
+        );
+        assert_eq!(
+            OutlineOverrideValue::Override(LinearRgba::RED).scale_offset(),
+            (Vec4::ZERO, LinearRgba::RED.to_vec4())
+        );
+    }
+}
@@ -1,19 +1,25 @@
+mod outline_override;
 mod phase_item;
 mod pipeline;
 mod render_command;
 mod view_node;
 
 use crate::error::vrm_error;
-use crate::vrm::mtoon::MToonMaterial;
+use crate::macros::marker_component;
+use crate::vrm::mtoon::outline_pass::outline_override::{
+    ExtractedOutlineOverrides, OutlineOverrideBuffer, extract_outline_overrides,
+    prepare_outline_overrides,
+};
 use crate::vrm::mtoon::outline_pass::phase_item::OutlinePhaseItem;
 use crate::vrm::mtoon::outline_pass::pipeline::MToonOutlinePipeline;
 use crate::vrm::mtoon::outline_pass::render_command::DrawOutline;
 use crate::vrm::mtoon::outline_pass::view_node::{OutlineDrawNode, OutlineDrawPassLabel};
+use crate::vrm::mtoon::{MToonMaterial, MToonMaterialKey};
 use bevy::pbr::{
     MaterialBindGroupAllocator, MaterialPipelineKey, PreparedMaterial, RenderMeshInstanceFlags,
     ViewKeyCache, alpha_mode_pipeline_key, queue_material_meshes,
 };
-use bevy::render::sync_world::MainEntityHashMap;
+use bevy::render::sync_world::{MainEntity, MainEntityHashMap};
 use bevy::render::view::RenderVisibilityRanges;
 use bevy::{
     core_pipeline::core_3d::graph::{Core3d, Node3d},
@@ -35,6 +41,13 @@ use bevy::{
     },
 };
 
+marker_component!(
+    /// A marker component that disables the outlines of [`MToonMaterial`] on the camera.
+    ///
+    /// This is useful for the cameras such as minimaps that should not draw the outlines.
+    NoMToonOutline
+);
+
 pub struct MToonOutlinePlugin;
 
 impl Plugin for MToonOutlinePlugin {
@@ -56,9 +69,17 @@ impl Plugin for MToonOutlinePlugin {
             .add_render_command::<OutlinePhaseItem, DrawOutline>()
             .init_resource::<ViewSortedRenderPhases<OutlinePhaseItem>>()
             .init_resource::<MToonMaterialInstances>()
+            .init_resource::<ExtractedOutlineOverrides>()
+            .init_resource::<OutlineOverrideBuffer>()
+            .init_resource::<OutlineEnabled>()
             .add_systems(
                 ExtractSchedule,
-                (extract_camera_phases, extract_mtoon_materials),
+                (
+                    extract_outline_enabled,
+                    extract_mtoon_materials,
+                    (extract_camera_phases, extract_outline_overrides).run_if(outline_enabled),
+                )
+                    .chain(),
             )
             .add_systems(
                 Render,
@@ -67,7 +88,9 @@ impl Plugin for MToonOutlinePlugin {
                         .after(queue_material_meshes::<MToonMaterial>)
                         .in_set(RenderSet::QueueMeshes),
                     sort_phase_system::<OutlinePhaseItem>.in_set(RenderSet::PhaseSort),
-                ),
+                    prepare_outline_overrides.in_set(RenderSet::PrepareBindGroups),
+                )
+                    .run_if(outline_enabled),
             );
 
         render_app
@@ -96,10 +119,31 @@ impl Plugin for MToonOutlinePlugin {
 #[derive(Resource, Default, Deref, DerefMut)]
 struct MToonMaterialInstances(MainEntityHashMap<AssetId<MToonMaterial>>);
 
+/// Whether any [`MToonMaterial`] draws the outline.
+///
+/// The outline pass is skipped entirely while this is `false`,
+/// such as when all VRMs are rendered with [`VrmMaterialMode::MToonLite`](crate::prelude::VrmMaterialMode::MToonLite)
+/// or [`VrmMaterialMode::Standard`](crate::prelude::VrmMaterialMode::Standard).
+#[derive(Resource, Default)]
+pub(super) struct OutlineEnabled(pub(super) bool);
+
+fn outline_enabled(enabled: Res<OutlineEnabled>) -> bool {
+    enabled.0
+}
+
+fn extract_outline_enabled(
+    mut enabled: ResMut<OutlineEnabled>,
+    materials: Extract<Res<Assets<MToonMaterial>>>,
+) {
+    enabled.0 = materials
+        .iter()
+        .any(|(_, material)| MToonMaterialKey::from(material).contains(MToonMaterialKey::OUTLINE));
+}
+
 fn extract_camera_phases(
     mut outline_phases: ResMut<ViewSortedRenderPhases<OutlinePhaseItem>>,
     mut live_entities: Local<HashSet<RetainedViewEntity>>,
-    cameras: Extract<Query<(Entity, &Camera), With<Camera3d>>>,
+    cameras: Extract<Query<(Entity, &Camera), (With<Camera3d>, Without<NoMToonOutline>)>>,
 ) {
     live_entities.clear();
     for (main_entity, camera) in &cameras {
@@ -117,11 +161,35 @@ fn extract_camera_phases(
 
 fn extract_mtoon_materials(
     mut instances: ResMut<MToonMaterialInstances>,
+    mut was_enabled: Local<bool>,
+    enabled: Res<OutlineEnabled>,
+    mut removed: Extract<RemovedComponents<MeshMaterial3d<MToonMaterial>>>,
     materials: Extract<Query<(Entity, &MeshMaterial3d<MToonMaterial>)>>,
+    changed_materials: Extract<
+        Query<(Entity, &MeshMaterial3d<MToonMaterial>), Changed<MeshMaterial3d<MToonMaterial>>>,
+    >,
 ) {
-    materials.iter().for_each(|(entity, material)| {
-        instances.0.insert(entity.into(), material.id());
-    });
+    // Removals are applied first so that a material removed and re-inserted in the same frame is kept.
+    for entity in removed.read() {
+        instances.remove(&MainEntity::from(entity));
+    }
+    let rebuild = enabled.0 && !*was_enabled;
+    *was_enabled = enabled.0;
+    if !enabled.0 {
+        instances.clear();
+        return;
+    }
+    if rebuild {
+        // The changes are not tracked while the outline is disabled, so all instances are extracted again.
+        instances.clear();
+        for (entity, material) in materials.iter() {
+            instances.insert(entity.into(), material.id());
+        }
+        return;
+    }
+    for (entity, material) in changed_materials.iter() {
+        instances.insert(entity.into(), material.id());
+    }
 }
 
 fn queue_outlines(
@@ -132,6 +200,7 @@ fn queue_outlines(
     view_key_cache: Res<ViewKeyCache>,
     render_visibility_ranges: Res<RenderVisibilityRanges>,
     instances: Res<MToonMaterialInstances>,
+    outline_overrides: Res<ExtractedOutlineOverrides>,
     render_materials: Res<RenderAssets<PreparedMaterial<MToonMaterial>>>,
     draw_functions: Res<DrawFunctions<OutlinePhaseItem>>,
     pipeline_cache: Res<PipelineCache>,
@@ -158,9 +227,22 @@ fn queue_outlines(
             let Some(asset_id) = instances.get(visible_entity) else {
                 continue;
             };
+            if outline_overrides
+                .get(visible_entity)
+                .is_some_and(|outline_override| !outline_override.visible)
+            {
+                continue;
+            }
             let Some(material) = render_materials.get(*asset_id) else {
                 continue;
             };
+            let bind_group_data = *material_bind_group_allocator
+                .get(material.binding.group)
+                .unwrap()
+                .get_extra_data(material.binding.slot);
+            if !bind_group_data.contains(MToonMaterialKey::OUTLINE) {
+                continue;
+            }
             let mut mesh_pipeline_key_bits = material.properties.mesh_pipeline_key_bits;
             mesh_pipeline_key_bits.insert(alpha_mode_pipeline_key(
                 material.properties.alpha_mode,
@@ -191,10 +273,7 @@ fn queue_outlines(
 
             let material_key = MaterialPipelineKey {
                 mesh_key,
-                bind_group_data: *material_bind_group_allocator
-                    .get(material.binding.group)
-                    .unwrap()
-                    .get_extra_data(material.binding.slot),
+                bind_group_data,
             };
 
             let pipeline_id = pipelines.specialize(
@@ -225,3 +304,101 @@ fn queue_outlines(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestExtract, test_app};
+    use crate::vrm::mtoon::outline_pass::{
+        MToonMaterialInstances, OutlineEnabled, extract_mtoon_materials,
+    };
+    use bevy::prelude::*;
+
+    #[test]
+    fn instances_are_removed_on_despawn() {
+        let mut app = test_app();
+        app.init_asset::<MToonMaterial>();
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<MToonMaterial>>()
+            .add(MToonMaterial::default());
+
+        let mut extract = TestExtract::new(extract_mtoon_materials);
+        extract
+            .render_world
+            .init_resource::<MToonMaterialInstances>();
+        extract.render_world.insert_resource(OutlineEnabled(true));
+        extract.run(&mut app);
+
+        for _ in 0..10 {
+            let avatars = (0..100)
+                .map(|_| {
+                    app.world_mut()
+                        .spawn_empty()
+                        .with_child(MeshMaterial3d(handle.clone()))
+                        .id()
+                })
+                .collect::<Vec<_>>();
+            app.update();
+            extract.run(&mut app);
+            assert_eq!(
+                extract
+                    .render_world
+                    .resource::<MToonMaterialInstances>()
+                    .len(),
+                100
+            );
+
+            for avatar in avatars {
+                app.world_mut().entity_mut(avatar).despawn();
+            }
+            app.update();
+            extract.run(&mut app);
+            assert!(
+                extract
+                    .render_world
+                    .resource::<MToonMaterialInstances>()
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn extract_instances_again_when_outline_is_enabled() {
+        let mut app = test_app();
+        app.init_asset::<MToonMaterial>();
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<MToonMaterial>>()
+            .add(MToonMaterial::default());
+
+        let mut extract = TestExtract::new(extract_mtoon_materials);
+        extract
+            .render_world
+            .init_resource::<MToonMaterialInstances>();
+        extract.render_world.insert_resource(OutlineEnabled(false));
+        extract.run(&mut app);
+
+        app.world_mut().spawn(MeshMaterial3d(handle));
+        app.update();
+        extract.run(&mut app);
+        assert!(
+            extract
+                .render_world
+                .resource::<MToonMaterialInstances>()
+                .is_empty()
+        );
+
+        // The mesh is no longer changed, but it must be extracted after the outline is enabled.
+        app.update();
+        extract.render_world.resource_mut::<OutlineEnabled>().0 = true;
+        extract.run(&mut app);
+        assert_eq!(
+            extract
+                .render_world
+                .resource::<MToonMaterialInstances>()
+                .len(),
+            1
+        );
+    }
+}
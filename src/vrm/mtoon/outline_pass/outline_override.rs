@@ -0,0 +1,102 @@
+use crate::vrm::mtoon::MToonMaterial;
+use crate::vrm::mtoon::outline_override::MToonOutlineOverride;
+use crate::vrm::mtoon::outline_pass::pipeline::MToonOutlinePipeline;
+use bevy::prelude::*;
+use bevy::render::Extract;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntries, DynamicUniformBuffer, ShaderType,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::sync_world::{MainEntity, MainEntityHashMap};
+
+/// The [`MToonOutlineOverride`]s of the meshes extracted from the main world.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(super) struct ExtractedOutlineOverrides(MainEntityHashMap<MToonOutlineOverride>);
+
+/// The overrides are applied as `value * scale + offset`,
+/// so the default value leaves the material as it is.
+#[derive(Clone, ShaderType)]
+pub(super) struct OutlineOverrideUniform {
+    width: Vec2,
+    lighting_mix_factor: Vec2,
+    color_scale: Vec4,
+    color_offset: Vec4,
+}
+
+impl From<&MToonOutlineOverride> for OutlineOverrideUniform {
+    fn from(outline_override: &MToonOutlineOverride) -> Self {
+        let (color_scale, color_offset) = outline_override.color.scale_offset();
+        Self {
+            width: outline_override.width.scale_offset(),
+            lighting_mix_factor: outline_override.lighting_mix_factor.scale_offset(),
+            color_scale,
+            color_offset,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub(super) struct OutlineOverrideBuffer {
+    uniforms: DynamicUniformBuffer<OutlineOverrideUniform>,
+    offsets: MainEntityHashMap<u32>,
+    default_offset: u32,
+    pub bind_group: Option<BindGroup>,
+}
+
+impl OutlineOverrideBuffer {
+    #[inline]
+    pub fn offset(
+        &self,
+        entity: &MainEntity,
+    ) -> u32 {
+        self.offsets
+            .get(entity)
+            .copied()
+            .unwrap_or(self.default_offset)
+    }
+}
+
+pub(super) fn extract_outline_overrides(
+    mut overrides: ResMut<ExtractedOutlineOverrides>,
+    vrms: Extract<Query<(Entity, &MToonOutlineOverride)>>,
+    childrens: Extract<Query<&Children>>,
+    meshes: Extract<Query<(), With<MeshMaterial3d<MToonMaterial>>>>,
+) {
+    overrides.clear();
+    for (vrm, outline_override) in vrms.iter() {
+        for entity in childrens.iter_descendants(vrm) {
+            if meshes.contains(entity) {
+                overrides.insert(entity.into(), *outline_override);
+            }
+        }
+    }
+}
+
+pub(super) fn prepare_outline_overrides(
+    mut buffer: ResMut<OutlineOverrideBuffer>,
+    overrides: Res<ExtractedOutlineOverrides>,
+    pipeline: Res<MToonOutlinePipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let buffer = buffer.as_mut();
+    buffer.uniforms.clear();
+    buffer.offsets.clear();
+    buffer.default_offset = buffer.uniforms.push(&OutlineOverrideUniform::from(
+        &MToonOutlineOverride::default(),
+    ));
+    for (entity, outline_override) in overrides.iter() {
+        let offset = buffer
+            .uniforms
+            .push(&OutlineOverrideUniform::from(outline_override));
+        buffer.offsets.insert(*entity, offset);
+    }
+    buffer.uniforms.write_buffer(&render_device, &render_queue);
+    buffer.bind_group = buffer.uniforms.binding().map(|binding| {
+        render_device.create_bind_group(
+            "mtoon_outline_override_bind_group",
+            &pipeline.override_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+}
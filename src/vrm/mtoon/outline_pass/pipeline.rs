@@ -1,21 +1,33 @@
 use crate::vrm::mtoon::MToonMaterial;
+use crate::vrm::mtoon::outline_pass::outline_override::OutlineOverrideUniform;
 use bevy::pbr::MaterialPipeline;
 use bevy::prelude::*;
 use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::binding_types::uniform_buffer;
 use bevy::render::render_resource::{
-    CompareFunction, Face, RenderPipelineDescriptor, SpecializedMeshPipeline,
-    SpecializedMeshPipelineError,
+    BindGroupLayout, BindGroupLayoutEntries, CompareFunction, Face, RenderPipelineDescriptor,
+    ShaderStages, SpecializedMeshPipeline, SpecializedMeshPipelineError,
 };
+use bevy::render::renderer::RenderDevice;
 
 #[derive(Resource)]
 pub(super) struct MToonOutlinePipeline {
     base: MaterialPipeline<MToonMaterial>,
+    pub override_layout: BindGroupLayout,
 }
 
 impl FromWorld for MToonOutlinePipeline {
     fn from_world(world: &mut World) -> Self {
+        let override_layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "mtoon_outline_override_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                uniform_buffer::<OutlineOverrideUniform>(true),
+            ),
+        );
         Self {
             base: MaterialPipeline::from_world(world),
+            override_layout,
         }
     }
 }
@@ -32,6 +44,7 @@ impl SpecializedMeshPipeline for MToonOutlinePipeline {
         let mut descriptor = self.base.specialize(key.clone(), layout)?;
         descriptor.label.replace("mtoon_outline_pipeline".into());
         descriptor.vertex.shader_defs.push(PASS_NAME.into());
+        descriptor.layout.push(self.override_layout.clone());
         if let Some(stencil) = descriptor.depth_stencil.as_mut() {
             stencil.depth_compare = CompareFunction::GreaterEqual;
         }
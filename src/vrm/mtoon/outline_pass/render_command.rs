@@ -1,11 +1,40 @@
 use crate::vrm::mtoon::MToonMaterial;
+use crate::vrm::mtoon::outline_pass::outline_override::OutlineOverrideBuffer;
+use bevy::ecs::system::SystemParamItem;
+use bevy::ecs::system::lifetimeless::SRes;
 use bevy::pbr::{DrawMesh, SetMaterialBindGroup, SetMeshBindGroup, SetMeshViewBindGroup};
-use bevy::render::render_phase::SetItemPipeline;
+use bevy::render::render_phase::{
+    PhaseItem, RenderCommand, RenderCommandResult, SetItemPipeline, TrackedRenderPass,
+};
 
 pub(super) type DrawOutline = (
     SetItemPipeline,
     SetMeshViewBindGroup<0>,
     SetMeshBindGroup<1>,
     SetMaterialBindGroup<MToonMaterial, 2>,
+    SetOutlineOverrideBindGroup<3>,
     DrawMesh,
 );
+
+pub(super) struct SetOutlineOverrideBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetOutlineOverrideBindGroup<I> {
+    type Param = SRes<OutlineOverrideBuffer>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        _entity: Option<()>,
+        buffer: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let buffer = buffer.into_inner();
+        let Some(bind_group) = buffer.bind_group.as_ref() else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, bind_group, &[buffer.offset(&item.main_entity())]);
+        RenderCommandResult::Success
+    }
+}
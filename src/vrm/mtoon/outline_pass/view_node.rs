@@ -1,4 +1,5 @@
 use crate::error::vrm_error;
+use crate::vrm::mtoon::outline_pass::OutlineEnabled;
 use crate::vrm::mtoon::outline_pass::phase_item::OutlinePhaseItem;
 use bevy::ecs::query::QueryItem;
 use bevy::prelude::World;
@@ -30,12 +31,21 @@ impl ViewNode for OutlineDrawNode {
         (camera, view, target, depth_texture): QueryItem<'w, Self::ViewQuery>,
         world: &'w World,
     ) -> bevy::prelude::Result<(), NodeRunError> {
+        if !world
+            .get_resource::<OutlineEnabled>()
+            .is_some_and(|enabled| enabled.0)
+        {
+            return Ok(());
+        }
         let Some(outline_phases) = world.get_resource::<ViewSortedRenderPhases<OutlinePhaseItem>>()
         else {
             return Ok(());
         };
 
-        if let Some(outline_pass) = outline_phases.get(&view.retained_view_entity) {
+        // The render pass is skipped entirely if no outline is queued.
+        if let Some(outline_pass) = outline_phases.get(&view.retained_view_entity)
+            && !outline_pass.items.is_empty()
+        {
             let view_entity = graph.view_entity();
             let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
                 label: Some("outline pass"),
@@ -1,6 +1,9 @@
+use crate::error::vrm_warn;
 use crate::prelude::*;
 use bevy::app::{App, Plugin};
 use bevy::asset::Assets;
+use bevy::core_pipeline::prepass::DeferredPrepass;
+use bevy::pbr::OpaqueRendererMethod;
 use bevy::prelude::*;
 
 pub struct MToonMaterialSetupPlugin;
@@ -19,6 +22,10 @@ fn turn_to_mtoon_material(
     mut mtoon_materials: ResMut<Assets<MToonMaterial>>,
     standard_materials: Res<Assets<StandardMaterial>>,
     registries: Query<&VrmcMaterialRegistry>,
+    material_modes: Query<&VrmMaterialMode>,
+    default_material_mode: Option<Res<VrmMaterialMode>>,
+    deferred_cameras: Query<(), With<DeferredPrepass>>,
+    mut warned_deferred: Local<bool>,
     parents: Query<&ChildOf>,
     added_materials: Query<
         (Entity, &MeshMaterial3d<StandardMaterial>),
@@ -30,12 +37,30 @@ fn turn_to_mtoon_material(
         let Ok(registry) = registries.get(root) else {
             return;
         };
+        let material_mode = material_modes.get(root).copied().unwrap_or_else(|_| {
+            default_material_mode
+                .as_deref()
+                .copied()
+                .unwrap_or_default()
+        });
+        if material_mode == VrmMaterialMode::Standard {
+            return;
+        }
         let Some(extension) = registry.materials.get(&handle.id()) else {
             return;
         };
         let Some(base) = standard_materials.get(handle.id()).cloned() else {
             return;
         };
+        if !*warned_deferred
+            && (base.opaque_render_method == OpaqueRendererMethod::Deferred
+                || !deferred_cameras.is_empty())
+        {
+            vrm_warn!(
+                "MToon does not support the deferred rendering, so it is rendered in the forward pass. Use `VrmMaterialMode::Standard` to render VRM in the deferred pass."
+            );
+            *warned_deferred = true;
+        }
         let mut cmd = commands.entity(entity);
         cmd.remove::<MeshMaterial3d<StandardMaterial>>()
             .insert(MeshMaterial3d(
@@ -51,7 +76,7 @@ fn turn_to_mtoon_material(
                         .cloned(),
                     shading_shift_texture: extension
                         .shading_shift_texture
-                        .and_then(|tex| registry.images.get(tex.index))
+                        .and_then(|tex| registry.images.get(tex.texture.index))
                         .cloned(),
                     matcap_texture: extension
                         .matcap_texture
@@ -75,12 +100,18 @@ fn turn_to_mtoon_material(
                     depth_bias: base.depth_bias,
                     render_queue_offset: extension.render_queue_offset_number,
                     transparent_with_z_write: extension.transparent_with_z_write,
-                    opaque_renderer_method: base.opaque_render_method,
+                    // VRM has no property for this, so it is opted out through `MToonMaterials` after loading.
+                    receive_shadows: true,
+                    lite: material_mode == VrmMaterialMode::MToonLite,
                     base_color: base.base_color,
                     cull_mode: base.cull_mode,
                     emissive: base.emissive,
                     emissive_texture: base.emissive_texture.clone(),
                     uv_transform: base.uv_transform,
+                    base_color_channel: base.base_color_channel.clone(),
+                    emissive_channel: base.emissive_channel.clone(),
+                    texture_transforms: MToonTextureTransforms::from(extension),
+                    ..default()
                 }),
             ));
     });
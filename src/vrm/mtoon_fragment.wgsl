@@ -5,13 +5,24 @@
     },
     pbr_fragment::pbr_input_from_vertex_output,
     pbr_types::PbrInput,
-    mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
-    shadows::fetch_directional_shadow,
+    mesh_view_types::{
+        DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
+        POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
+        POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE,
+    },
+    mesh_types::MESH_FLAGS_SHADOW_RECEIVER_BIT,
+    shadows::{
+        fetch_directional_shadow,
+        fetch_point_shadow,
+        fetch_spot_shadow,
+    },
+    clustered_forward,
     ambient::ambient_light,
     mesh_view_bindings::{
         view,
         lights,
         globals,
+        clusterable_objects,
     },
 }
 #import mtoon::types::{
@@ -44,7 +55,26 @@
     ALPHA_MODE_BLEND,
     ALPHA_MODE_ALPHA_TO_COVERAGE,
     OUTLINE_WORLD_COORDINATES,
+    OUTLINE_SCREEN_COORDINATES,
+    RECEIVE_SHADOWS,
+    LIGHTING_MAIN_LIGHT_OVERRIDE,
+    LIGHTING_TOONY_FACTOR_OVERRIDE,
+    is_alpha_cutoff,
+    lighting_settings,
+    texture_uv,
+}
+#ifdef OUTLINE_PASS
+#import mtoon::types::outline_override
+#endif
+#ifdef ENVIRONMENT_MAP
+#import bevy_pbr::{
+    environment_map,
+    lighting::LayerLightingInput,
 }
+#endif
+#ifdef IRRADIANCE_VOLUME
+#import bevy_pbr::irradiance_volume
+#endif
 
 @fragment
 fn fragment(
@@ -52,23 +82,21 @@ fn fragment(
     @builtin(front_facing) is_front: bool,
 ) -> FragmentOutput {
 #ifdef OUTLINE_PASS
-    // Currently, txhe outline only supports world coordinates.
-    if((material.outline_flags & OUTLINE_WORLD_COORDINATES) == 0u) {
+    // The outline is not drawn if the outline width mode is none.
+    if((material.outline_flags & (OUTLINE_WORLD_COORDINATES | OUTLINE_SCREEN_COORDINATES)) == 0u) {
         discard;
     }
 #endif
 
-    var vertex_input = in;
-    vertex_input.uv = calc_animated_uv((material.uv_transform * vec3(in.uv, 1.0)).xy);
-
     var out: FragmentOutput;
-    var pbr_input = make_pbr_input(vertex_input, is_front);
-    let mtoon_input = make_mtoon_input(vertex_input, pbr_input);
+    var pbr_input = make_pbr_input(in, is_front);
+    let mtoon_input = make_mtoon_input(in, pbr_input);
     out.color = apply_mtoon_lighting(mtoon_input);
 
 #ifdef OUTLINE_PASS
-    let outline_color = material.outline_color.rgb * mix(vec3(1.), out.color.rgb, material.outline_lighting_mix_factor);
-    out.color = vec4(outline_color, mtoon_input.lit_color.a);
+    let outline_color = material.outline_color * outline_override.color_scale + outline_override.color_offset;
+    let lighting_mix_factor = material.outline_lighting_mix_factor * outline_override.lighting_mix_factor.x + outline_override.lighting_mix_factor.y;
+    out.color = vec4(outline_color.rgb * mix(vec3(1.), out.color.rgb, lighting_mix_factor), mtoon_input.lit_color.a);
 #endif
 
     return out;
@@ -80,7 +108,6 @@ fn make_pbr_input(
 ) -> PbrInput{
     let double_sided = (material.flags & DOUBLE_SIDED) != 0;
     var pbr_input = pbr_input_from_vertex_output(vertex_input, is_front, double_sided);
-    pbr_input.material.base_color = lit_color(vertex_input.uv);
     pbr_input.material.metallic = 0.0;
     pbr_input.material.emissive = material.emissive_color;
     return pbr_input;
@@ -92,9 +119,7 @@ fn lit_color(uv: vec2<f32>) -> vec4<f32> {
         base_color *= textureSampleBias(base_color_texture, base_color_sampler, uv, view.mip_bias);
     }
     if((material.flags & ALPHA_MODE_MASK) != 0u || (material.flags & ALPHA_MODE_ALPHA_TO_COVERAGE) != 0u) {
-        let raw = base_color.a;
-        let tmpAlpha = (raw - material.alpha_cutoff) / max(fwidth(raw), 0.00001) + 0.5;
-        if(tmpAlpha < material.alpha_cutoff) {
+        if(is_alpha_cutoff(base_color.a)) {
             discard;
         }else{
             base_color.a = 1.0;
@@ -109,19 +134,43 @@ fn lit_color(uv: vec2<f32>) -> vec4<f32> {
 }
 
 fn make_mtoon_input(in: VertexOutput, pbr_input: PbrInput) -> MToonInput{
-    let uv = in.uv;
-    return MToonInput(
+#ifdef VERTEX_UVS_B
+    let uv_b = in.uv_b;
+#else
+    let uv_b = in.uv;
+#endif
+    var mtoon_input = MToonInput(
         pbr_input,
-        uv,
+        in.uv,
+        uv_b,
+        0.0,
         pbr_input.V,
         in.world_position,
         pbr_input.N,
-        pbr_input.material.base_color,
+        vec4(0.),
+        lighting_settings,
+    );
+#ifndef MTOON_LITE
+    mtoon_input.uv_animation_time = calc_uv_time(
+        texture_uv(in.uv, uv_b, UV_ANIMATION_MASK_TEXTURE, material.uv_animation_mask_uv_transform)
     );
+#endif
+    mtoon_input.lit_color = lit_color(mtoon_texture_uv(mtoon_input, BASE_COLOR_TEXTURE, material.uv_transform));
+    mtoon_input.pbr.material.base_color = mtoon_input.lit_color;
+    return mtoon_input;
+}
+
+// Returns the UV to sample the texture, with the UV animation applied.
+fn mtoon_texture_uv(in: MToonInput, texture: u32, transform: mat3x3<f32>) -> vec2<f32> {
+    let uv = texture_uv(in.uv, in.uv_b, texture, transform);
+#ifdef MTOON_LITE
+    return uv;
+#else
+    return calc_animated_uv(uv, in.uv_animation_time);
+#endif
 }
 
-fn calc_animated_uv(uv: vec2<f32>) -> vec2<f32>{
-    let time = calc_uv_time(uv);
+fn calc_animated_uv(uv: vec2<f32>, time: f32) -> vec2<f32>{
     let translate = time * vec2(material.uv_animation_scroll_speed_x, material.uv_animation_rotation_speed_y);
     let rotate_rad = fract(time * material.uv_animation_rotation_speed);
     let cos_rotate = cos(rotate_rad);
@@ -140,66 +189,151 @@ fn calc_uv_time(uv: vec2<f32>) -> f32{
 }
 
 fn apply_mtoon_lighting(in: MToonInput) -> vec4<f32> {
-    let direct = apply_directional_lights(in);
+    let direct = apply_direct_lights(in);
     let in_direct = apply_global_illumination(in);
     let emissive = apply_emissive_light(in);
-    let rim = apply_rim_lighting(in.pbr, in.uv, direct, in_direct);
-    return vec4<f32>(direct + in_direct + emissive + rim, in.lit_color.a);
+#ifdef MTOON_LITE
+    let rim = vec3(0.);
+#else
+    let rim = apply_rim_lighting(
+        in.pbr,
+        mtoon_texture_uv(in, RIM_MAP_TEXTURE, material.rim_multiply_uv_transform),
+        direct,
+        in_direct,
+    );
+#endif
+    let lighting = min(direct + in_direct, vec3(in.lighting.max_intensity));
+    return vec4<f32>(lighting + emissive + rim, in.lit_color.a);
 }
 
-fn apply_directional_lights(in: MToonInput) -> vec3<f32>{
-    var direct: vec3<f32> = vec3(0.);
-    var shade_color: vec3<f32> = calc_shade_color(in);
+fn apply_direct_lights(in: MToonInput) -> vec3<f32>{
+    let shade_color = calc_shade_color(in);
+    if ((in.lighting.flags & LIGHTING_MAIN_LIGHT_OVERRIDE) != 0u) {
+        let shading = calc_mtoon_lighting_shading(in, dot(in.world_normal, in.lighting.main_light_direction), 1.0);
+        return mix(shade_color, in.lit_color.rgb, saturate(shading)) * in.lighting.main_light_color;
+    }
+    let view_z = calc_view_z(in);
+#ifdef MTOON_LITE
+    let shading = calc_directional_lights_shading(in, view_z);
+#else
+    let shading = calc_directional_lights_shading(in, view_z) + calc_clustered_lights_shading(in, view_z);
+#endif
+    return mix(shade_color, in.lit_color.rgb, saturate(shading));
+}
+
+fn calc_view_z(in: MToonInput) -> f32 {
+    return dot(vec4<f32>(
+        view.view_from_world[0].z,
+        view.view_from_world[1].z,
+        view.view_from_world[2].z,
+        view.view_from_world[3].z
+    ), in.world_position);
+}
+
+fn calc_directional_lights_shading(
+    in: MToonInput,
+    view_z: f32,
+) -> f32 {
     var shading: f32 = 0.0;
     for (var i: u32 = 0u; i < lights.n_directional_lights; i = i + 1u) {
-        if (lights.directional_lights[i].flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) == 0u {
+        let light = &lights.directional_lights[i];
+        var shadow: f32 = 1.0;
+#ifndef OUTLINE_PASS
+#ifndef MTOON_LITE
+        if (receives_shadows(in) && ((*light).flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+            shadow = fetch_directional_shadow(i, in.world_position, in.world_normal, view_z);
+        }
+#endif
+#endif
+        shading += calc_mtoon_lighting_shading(in, dot(in.world_normal, (*light).direction_to_light), shadow);
+    }
+    return shading;
+}
+
+// Point and spot lights are evaluated in the same way as directional lights,
+// except that the shading is faded out by the range of each light.
+fn calc_clustered_lights_shading(
+    in: MToonInput,
+    view_z: f32,
+) -> f32 {
+    let cluster_index = clustered_forward::fragment_cluster_index(in.pbr.frag_coord.xy, view_z, in.pbr.is_orthographic);
+    let ranges = clustered_forward::unpack_clusterable_object_index_ranges(cluster_index);
+    var shading: f32 = 0.0;
+    for (var i: u32 = ranges.first_point_light_index_offset; i < ranges.first_reflection_probe_index_offset; i = i + 1u) {
+        let light_id = clustered_forward::get_clusterable_object_id(i);
+        let light = &clusterable_objects.data[light_id];
+        let light_to_frag = (*light).position_radius.xyz - in.world_position.xyz;
+        let factor = dot(light_to_frag, light_to_frag) * (*light).color_inverse_square_range.w;
+        let range_attenuation = saturate(1.0 - factor * factor);
+        var attenuation = range_attenuation * range_attenuation;
+        let is_spot_light = ranges.first_spot_light_index_offset <= i;
+        if (is_spot_light) {
+            attenuation *= calc_spot_attenuation(light_id, light_to_frag);
+        }
+        if (attenuation <= 0.0) {
             continue;
         }
-        shading += calc_mtoon_lighting_shading(in, i);
+        var shadow: f32 = 1.0;
+#ifndef OUTLINE_PASS
+        if (receives_shadows(in) && ((*light).flags & POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+            if (is_spot_light) {
+                shadow = fetch_spot_shadow(light_id, in.world_position, in.world_normal, (*light).shadow_map_near_z);
+            } else {
+                shadow = fetch_point_shadow(light_id, in.world_position, in.world_normal);
+            }
+        }
+#endif
+        let n_dot_l = dot(in.world_normal, normalize(light_to_frag));
+        shading += calc_mtoon_lighting_shading(in, n_dot_l, shadow) * attenuation;
+    }
+    return shading;
+}
+
+fn calc_spot_attenuation(
+    light_id: u32,
+    light_to_frag: vec3<f32>,
+) -> f32 {
+    let light = &clusterable_objects.data[light_id];
+    var spot_dir = vec3<f32>((*light).light_custom_data.x, 0.0, (*light).light_custom_data.y);
+    spot_dir.y = sqrt(max(0.0, 1.0 - spot_dir.x * spot_dir.x - spot_dir.z * spot_dir.z));
+    if ((*light).flags & POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE) != 0u {
+        spot_dir.y = -spot_dir.y;
     }
-    return mix(shade_color, in.lit_color.rgb, shading);
+    let cd = dot(-spot_dir, normalize(light_to_frag));
+    let attenuation = saturate(cd * (*light).light_custom_data.z + (*light).light_custom_data.w);
+    return attenuation * attenuation;
 }
 
+fn receives_shadows(in: MToonInput) -> bool {
+    return (in.pbr.flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u && (material.flags & RECEIVE_SHADOWS) != 0u;
+}
+
+// The shadow attenuation is applied before the toon ramp,
+// so that the shadow boundary is also affected by `shadingToonyFactor` and `shadingShiftFactor`.
 fn calc_mtoon_lighting_shading(
     input: MToonInput,
-    light_id: u32,
+    n_dot_l: f32,
+    shadow: f32,
 ) -> f32 {
-    let light = &lights.directional_lights[light_id];
-    let light_color = (*light).color.rgb;
-    let NdotL = saturate(dot(input.world_normal, (*light).direction_to_light));
     let shade_shift = calc_mtoon_lighting_reflectance_shading_shift(input);
-#ifdef OUTLINE_PASS
-    let shading = mtoon_linearstep(-1.0 + material.shading_toony_factor, 1.0 - material.shading_toony_factor, NdotL + shade_shift);
-#else
-    let shade_input = mix(-1., 1., mtoon_linearstep(-1., 1., NdotL));
-    let view_z = dot(vec4<f32>(
-        view.view_from_world[0].z,
-        view.view_from_world[1].z,
-        view.view_from_world[2].z,
-        view.view_from_world[3].z
-    ), input.world_position);
-    var shadow = fetch_directional_shadow(
-        light_id,
-        input.world_position,
-        input.world_normal,
-        view_z,
-    );
-    let shading =  mtoon_linearstep(-1.0 + material.shading_toony_factor, 1.0 - material.shading_toony_factor, shade_input + shade_shift) * shadow;
-#endif
-   return shading;
+    let shade_input = mix(-1., 1., mtoon_linearstep(-1., 1., n_dot_l) * shadow);
+    var toony_factor = material.shading_toony_factor;
+    if ((input.lighting.flags & LIGHTING_TOONY_FACTOR_OVERRIDE) != 0u) {
+        toony_factor = input.lighting.shading_toony_factor;
+    }
+    return mtoon_linearstep(-1.0 + toony_factor, 1.0 - toony_factor, shade_input + shade_shift);
 }
 
 fn calc_mtoon_lighting_reflectance_shading_shift(
     input: MToonInput,
 ) -> f32 {
     if((material.flags & SHADING_SHIFT_TEXTURE) != 0u) {
-        return textureSampleBias(shading_shift_texture, shading_shift_texture_sampler, input.uv, view.mip_bias).r * material.shading_shift_texture_scale + material.shading_shift_factor;
+        return textureSampleBias(shading_shift_texture, shading_shift_texture_sampler, mtoon_texture_uv(input, SHADING_SHIFT_TEXTURE, material.shading_shift_uv_transform), view.mip_bias).r * material.shading_shift_texture_scale + material.shading_shift_factor;
     } else {
         return material.shading_shift_factor;
     }
 }
 
-//FIXME: This code is likely an incomplete implementation.
 // https://github.com/vrm-c/vrm-specification/blob/master/specification/VRMC_materials_mtoon-1.0/README.md#lighting
 fn apply_global_illumination(
     in: MToonInput,
@@ -212,25 +346,81 @@ fn apply_global_illumination(
         base_color,
         in.pbr.material.diffuse_transmission,
     );
-    let in_direct_light = ambient_light(
+    // The global illumination is flattened by `giEqualizationFactor`,
+    // by blending the irradiance in the normal direction with the average of the upward and downward irradiance.
+    let passthrough_gi = calc_global_illumination(in, in.world_normal, diffuse_color);
+    let uniformed_gi = 0.5 * (
+        calc_global_illumination(in, vec3(0., 1., 0.), diffuse_color) +
+        calc_global_illumination(in, vec3(0., -1., 0.), diffuse_color)
+    );
+    let gi = mix(passthrough_gi, uniformed_gi, material.gi_equalization_factor);
+    // The ambient of `MToonLightingSettings` does not depend on the direction, so it is not equalized.
+    return view.exposure * gi + in.lighting.ambient * diffuse_color * in.pbr.diffuse_occlusion;
+#endif
+}
+
+// Returns the sum of the ambient light and the irradiance of the light probes in the direction `N`.
+fn calc_global_illumination(
+    in: MToonInput,
+    N: vec3<f32>,
+    diffuse_color: vec3<f32>,
+) -> vec3<f32> {
+    var gi = ambient_light(
         in.world_position,
-        in.world_normal,
+        N,
         in.world_view_dir,
-        dot(in.world_normal, in.world_view_dir),
+        max(dot(N, in.world_view_dir), 0.0001),
         diffuse_color,
         // Is the reflection color unnecessary?
         vec3(0.),
         in.pbr.material.perceptual_roughness,
         in.pbr.diffuse_occlusion,
     );
-    return view.exposure * in_direct_light;
+#ifndef MTOON_LITE
+    gi += calc_light_probes_gi(in, N) * diffuse_color * in.pbr.diffuse_occlusion;
+#endif
+    return gi;
+}
+
+fn calc_light_probes_gi(
+    in: MToonInput,
+    N: vec3<f32>,
+) -> vec3<f32> {
+    let cluster_index = clustered_forward::fragment_cluster_index(in.pbr.frag_coord.xy, calc_view_z(in), in.pbr.is_orthographic);
+    var ranges = clustered_forward::unpack_clusterable_object_index_ranges(cluster_index);
+    return light_probe_irradiance(in.world_position.xyz, N, &ranges);
+}
+
+// Like `StandardMaterial`, the irradiance volumes take precedence over the environment maps.
+fn light_probe_irradiance(
+    world_position: vec3<f32>,
+    N: vec3<f32>,
+    ranges: ptr<function, clustered_forward::ClusterableObjectIndexRanges>,
+) -> vec3<f32> {
+#ifdef IRRADIANCE_VOLUME
+    let irradiance = irradiance_volume::irradiance_volume_light(world_position, N, ranges);
+    if (any(irradiance != vec3(0.))) {
+        return irradiance;
+    }
+#endif
+#ifdef ENVIRONMENT_MAP
+    // Only the diffuse irradiance is used, so the roughness and the reflection vector are not relevant.
+    var input: LayerLightingInput;
+    input.N = N;
+    input.R = N;
+    input.NdotV = 1.;
+    input.perceptual_roughness = 1.;
+    input.roughness = 1.;
+    return environment_map::compute_radiances(input, ranges, world_position, false).irradiance;
+#else
+    return vec3(0.);
 #endif
 }
 
 fn calc_shade_color(in: MToonInput) -> vec3<f32>{
    let base_color = material.shade_color.rgb;
    if((material.flags & SHADE_MULTIPLY_TEXTURE) != 0u) {
-       return base_color * textureSampleBias(shade_multiply_texture, shade_multiply_texture_sampler, in.uv, view.mip_bias).rgb;
+       return base_color * textureSampleBias(shade_multiply_texture, shade_multiply_texture_sampler, mtoon_texture_uv(in, SHADE_MULTIPLY_TEXTURE, material.shade_multiply_uv_transform), view.mip_bias).rgb;
    }else{
       return base_color;
    }
@@ -241,8 +431,9 @@ fn apply_emissive_light(in: MToonInput) -> vec3<f32> {
     return vec3(0.);
 #else
     let emissive = in.pbr.material.emissive.rgb;
-    if ((in.pbr.flags & EMISSIVE_TEXTURE) != 0u) {
-        return emissive * textureSampleBias(emissive_texture, emissive_sampler, in.uv, view.mip_bias).rgb;
+    if ((material.flags & EMISSIVE_TEXTURE) != 0u) {
+        let uv = mtoon_texture_uv(in, EMISSIVE_TEXTURE, material.uv_transform);
+        return emissive * textureSampleBias(emissive_texture, emissive_sampler, uv, view.mip_bias).rgb;
     } else {
         return emissive;
     }
@@ -254,9 +445,10 @@ fn apply_rim_lighting(in: PbrInput, uv: vec2<f32>, direct_light: vec3<f32>, in_d
     let world_view_x = normalize(vec3<f32>(in.V.z, 0.0, -in.V.x));
     let world_view_y = cross(in.V, world_view_x);
     let matcap_uv = vec2<f32>(dot(world_view_x, in.N), dot(world_view_y, in.N)) * 0.495 + 0.5;
+    let matcap_texture_uv = (material.matcap_uv_transform * vec3(matcap_uv, 1.0)).xy;
     let epsilon = 0.0001;
     if((material.flags & MATCAP_TEXTURE) != 0u) {
-        rim = material.mat_cap_color.rgb * textureSampleBias(matcap_texture, matcap_sampler, matcap_uv, view.mip_bias).rgb;
+        rim = material.mat_cap_color.rgb * textureSampleBias(matcap_texture, matcap_sampler, matcap_texture_uv, view.mip_bias).rgb;
     }
 
     let parametric_rim = saturate(1.0 - dot(in.N, in.V) + material.parametric_rim_lift_factor);
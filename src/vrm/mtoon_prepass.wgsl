@@ -0,0 +1,72 @@
+#import bevy_pbr::{
+    prepass_bindings,
+    prepass_io::VertexOutput,
+    prepass_io,
+    mesh_view_bindings::view,
+}
+#import mtoon::types::{
+    material,
+    base_color_texture,
+    base_color_sampler,
+    BASE_COLOR_TEXTURE,
+    ALPHA_MODE_MASK,
+    ALPHA_MODE_ALPHA_TO_COVERAGE,
+    is_alpha_cutoff,
+    texture_uv,
+}
+
+#ifdef PREPASS_FRAGMENT
+@fragment
+fn fragment(in: VertexOutput) -> prepass_io::FragmentOutput {
+    alpha_discard(in);
+
+    var out: prepass_io::FragmentOutput;
+
+#ifdef NORMAL_PREPASS
+    out.normal = vec4(in.world_normal * 0.5 + vec3(0.5), 1.0);
+#endif
+
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.frag_depth = in.unclipped_depth;
+#endif
+
+#ifdef MOTION_VECTOR_PREPASS
+    let clip_position_t = view.unjittered_clip_from_world * in.world_position;
+    let clip_position = clip_position_t.xy / clip_position_t.w;
+    let previous_clip_position_t = prepass_bindings::previous_view_uniforms.clip_from_world * in.previous_world_position;
+    let previous_clip_position = previous_clip_position_t.xy / previous_clip_position_t.w;
+    out.motion_vector = (clip_position - previous_clip_position) * vec2(0.5, -0.5);
+#endif
+
+    return out;
+}
+#else
+@fragment
+fn fragment(in: VertexOutput) {
+    alpha_discard(in);
+}
+#endif
+
+// Discards the fragment in the same way as the main pass,
+// so that the cutout parts do not write depth or cast shadows.
+fn alpha_discard(in: VertexOutput) {
+#ifdef MAY_DISCARD
+    if((material.flags & (ALPHA_MODE_MASK | ALPHA_MODE_ALPHA_TO_COVERAGE)) == 0u) {
+        return;
+    }
+    var alpha = material.base_color.a;
+#ifdef VERTEX_UVS_A
+    if((material.flags & BASE_COLOR_TEXTURE) != 0u) {
+#ifdef VERTEX_UVS_B
+        let uv = texture_uv(in.uv, in.uv_b, BASE_COLOR_TEXTURE, material.uv_transform);
+#else
+        let uv = texture_uv(in.uv, in.uv, BASE_COLOR_TEXTURE, material.uv_transform);
+#endif
+        alpha *= textureSampleBias(base_color_texture, base_color_sampler, uv, view.mip_bias).a;
+    }
+#endif
+    if(is_alpha_cutoff(alpha)) {
+        discard;
+    }
+#endif
+}
@@ -8,7 +8,6 @@ struct MToonMaterialUniform {
     shade_color: vec4<f32>,
     emissive_color: vec4<f32>,
     shading_shift_factor: f32,
-    shading_shift_texture_offset: f32,
     shading_shift_texture_scale: f32,
     shading_toony_factor: f32,
     gi_equalization_factor: f32,
@@ -26,15 +25,45 @@ struct MToonMaterialUniform {
     outline_color: vec4<f32>,
     outline_width_factor: f32,
     outline_lighting_mix_factor: f32,
+    shading_shift_uv_transform: mat3x3<f32>,
+    shade_multiply_uv_transform: mat3x3<f32>,
+    rim_multiply_uv_transform: mat3x3<f32>,
+    uv_animation_mask_uv_transform: mat3x3<f32>,
+    matcap_uv_transform: mat3x3<f32>,
+    outline_width_multiply_uv_transform: mat3x3<f32>,
+    // The textures that use the second UV set.
+    uv_channels: u32,
+}
+
+// The per-avatar overrides of the outline, applied as `value * scale + offset`.
+struct OutlineOverride {
+    width: vec2<f32>,
+    lighting_mix_factor: vec2<f32>,
+    color_scale: vec4<f32>,
+    color_offset: vec4<f32>,
 }
 
 struct MToonInput{
     pbr: PbrInput,
     uv: vec2<f32>,
+    uv_b: vec2<f32>,
+    uv_animation_time: f32,
     world_view_dir: vec3<f32>,
     world_position: vec4<f32>,
     world_normal: vec3<f32>,
     lit_color: vec4<f32>,
+    lighting: MToonLightingSettings,
+};
+
+// The global overrides of the lighting, bound from `MToonLightingSettings`.
+struct MToonLightingSettings {
+    flags: u32,
+    // The direction from the surface to the main light.
+    main_light_direction: vec3<f32>,
+    main_light_color: vec3<f32>,
+    shading_toony_factor: f32,
+    ambient: vec3<f32>,
+    max_intensity: f32,
 };
 
 @group(2) @binding(100) var<uniform> material: MToonMaterialUniform;
@@ -54,6 +83,7 @@ struct MToonInput{
 @group(2) @binding(114) var emissive_sampler: sampler;
 @group(2) @binding(115) var outline_width_multiply_texture: texture_2d<f32>;
 @group(2) @binding(116) var outline_width_multiply_sampler: sampler;
+@group(2) @binding(117) var<uniform> lighting_settings: MToonLightingSettings;
 
 const BASE_COLOR_TEXTURE: u32 = 1u;
 const SHADING_SHIFT_TEXTURE: u32 = 2u;
@@ -67,6 +97,31 @@ const ALPHA_MODE_MASK: u32 = 256u;
 const ALPHA_MODE_ALPHA_TO_COVERAGE: u32 = 512u;
 const ALPHA_MODE_BLEND: u32 = 1024u;
 const OUTLINE_WIDTH_MULTIPLY_TEXTURE: u32 = 2048u;
+const RECEIVE_SHADOWS: u32 = 4096u;
+
+// Lighting settings flags
+const LIGHTING_MAIN_LIGHT_OVERRIDE: u32 = 1u;
+const LIGHTING_TOONY_FACTOR_OVERRIDE: u32 = 2u;
 
 // Outline flags
-const OUTLINE_WORLD_COORDINATES: u32 = 1u;
\ No newline at end of file
+const OUTLINE_WORLD_COORDINATES: u32 = 1u;
+const OUTLINE_SCREEN_COORDINATES: u32 = 2u;
+
+// Selects the UV set of the texture and applies its `KHR_texture_transform`.
+fn texture_uv(uv: vec2<f32>, uv_b: vec2<f32>, texture: u32, transform: mat3x3<f32>) -> vec2<f32> {
+    var texcoord = uv;
+    if((material.uv_channels & texture) != 0u) {
+        texcoord = uv_b;
+    }
+    return (transform * vec3(texcoord, 1.0)).xy;
+}
+
+// Returns whether the fragment should be discarded by `alphaCutoff`.
+fn is_alpha_cutoff(alpha: f32) -> bool {
+    let tmp_alpha = (alpha - material.alpha_cutoff) / max(fwidth(alpha), 0.00001) + 0.5;
+    return tmp_alpha < material.alpha_cutoff;
+}
+
+#ifdef OUTLINE_PASS
+@group(3) @binding(0) var<uniform> outline_override: OutlineOverride;
+#endif
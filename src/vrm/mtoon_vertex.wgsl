@@ -8,6 +8,7 @@
     morph::morph,
     forward_io::{Vertex, VertexOutput},
     view_transformations::position_world_to_clip,
+    mesh_view_bindings::view,
 }
 #import mtoon::types::{
     MToonMaterialUniform,
@@ -15,7 +16,11 @@
     outline_width_multiply_texture,
     outline_width_multiply_sampler,
     OUTLINE_WIDTH_MULTIPLY_TEXTURE,
+    texture_uv,
 }
+#ifdef OUTLINE_PASS
+#import mtoon::types::outline_override
+#endif
 
 @vertex
 fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
@@ -53,11 +58,26 @@ fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
 #ifdef VERTEX_POSITIONS
     out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
 #ifdef OUTLINE_PASS
-    let outline_width = outline_width(vertex.uv);
+    let width_factor = material.outline_width_factor * outline_override.width.x + outline_override.width.y;
+#ifdef VERTEX_UVS_B
+    let outline_width = outline_width(vertex.uv, vertex.uv_b, width_factor);
+#else
+    let outline_width = outline_width(vertex.uv, vertex.uv, width_factor);
+#endif
+#ifdef OUTLINE_SCREEN_COORDINATES
+    out.position = expand_outline_in_clip_space(
+        position_world_to_clip(out.world_position.xyz),
+        out.world_normal,
+        outline_width,
+    );
+#else
     out.world_position = vec4(out.world_position.xyz + out.world_normal.xyz * outline_width, 1.0);
+    out.position = position_world_to_clip(out.world_position.xyz);
 #endif
+#else
     out.position = position_world_to_clip(out.world_position.xyz);
 #endif
+#endif
 
 #ifdef VERTEX_UVS_A
     out.uv = vertex.uv;
@@ -90,11 +110,11 @@ fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
     return out;
 }
 
-fn outline_width(uv: vec2<f32>) -> f32{
-    let w = material.outline_width_factor;
+fn outline_width(uv: vec2<f32>, uv_b: vec2<f32>, w: f32) -> f32{
     if ((material.flags & OUTLINE_WIDTH_MULTIPLY_TEXTURE) != 0) {
         let dims: vec2<u32> = textureDimensions(outline_width_multiply_texture, 0);
-        let coord = vec2<u32>(uv * vec2<f32>(dims));
+        let width_uv = texture_uv(uv, uv_b, OUTLINE_WIDTH_MULTIPLY_TEXTURE, material.outline_width_multiply_uv_transform);
+        let coord = vec2<u32>(width_uv * vec2<f32>(dims));
         let texel: vec4<f32> = textureLoad(
             outline_width_multiply_texture,
             coord,
@@ -106,6 +126,23 @@ fn outline_width(uv: vec2<f32>) -> f32{
     }
 }
 
+// Expands the vertex along the normal projected onto the screen.
+// The outline width is the ratio to the viewport height.
+fn expand_outline_in_clip_space(
+    position: vec4<f32>,
+    world_normal: vec3<f32>,
+    outline_width: f32,
+) -> vec4<f32> {
+    let viewport_size = view.viewport.zw;
+    let clip_normal = (view.clip_from_world * vec4(world_normal, 0.0)).xy * viewport_size;
+    if (dot(clip_normal, clip_normal) < 1e-12) {
+        return position;
+    }
+    let screen_normal = normalize(clip_normal);
+    let offset = 2.0 * outline_width * screen_normal * vec2(viewport_size.y / viewport_size.x, 1.0);
+    return vec4(position.xy + offset * position.w, position.zw);
+}
+
 #ifdef MORPH_TARGETS
 fn morph_vertex(vertex_in: Vertex) -> Vertex {
     var vertex = vertex_in;
@@ -0,0 +1,199 @@
+use crate::vrm::Vrm;
+use bevy::prelude::*;
+use bevy::render::view::{RenderLayers, VisibilitySystems};
+
+/// Propagates the [`RenderLayers`] inserted into the entity of VRM to its meshes.
+///
+/// Since the visibility of each view is determined by the render layers of the mesh entities,
+/// this makes the avatar and its outline visible only from the cameras that share the layers.
+///
+/// The meshes that have their own [`RenderLayers`], such as the head hidden from the first-person camera,
+/// keep them as they are.
+pub(super) struct VrmRenderLayersPlugin;
+
+impl Plugin for VrmRenderLayersPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.add_systems(
+            PostUpdate,
+            (
+                remove_render_layers,
+                propagate_changed_render_layers,
+                propagate_render_layers_to_added_meshes,
+            )
+                .chain()
+                .before(VisibilitySystems::CheckVisibility),
+        );
+    }
+}
+
+/// The marker that the [`RenderLayers`] of the mesh are propagated from VRM.
+///
+/// It holds the propagated layers, so that the layers changed from the outside are treated as the mesh's own.
+#[derive(Component)]
+struct InheritedVrmRenderLayers(RenderLayers);
+
+type MeshRenderLayers = (
+    Option<&'static RenderLayers>,
+    Option<&'static InheritedVrmRenderLayers>,
+);
+
+/// Returns whether the layers of the mesh can be replaced by the ones of VRM.
+fn is_inherited(
+    (layers, inherited): (Option<&RenderLayers>, Option<&InheritedVrmRenderLayers>)
+) -> bool {
+    match layers {
+        Some(layers) => inherited.is_some_and(|inherited| inherited.0 == *layers),
+        None => true,
+    }
+}
+
+fn propagate_changed_render_layers(
+    mut commands: Commands,
+    vrms: Query<(Entity, &RenderLayers), (With<Vrm>, Changed<RenderLayers>)>,
+    childrens: Query<&Children>,
+    meshes: Query<MeshRenderLayers, With<Mesh3d>>,
+) {
+    for (vrm, layers) in vrms.iter() {
+        for entity in childrens.iter_descendants(vrm) {
+            if meshes.get(entity).is_ok_and(is_inherited) {
+                commands
+                    .entity(entity)
+                    .insert((layers.clone(), InheritedVrmRenderLayers(layers.clone())));
+            }
+        }
+    }
+}
+
+fn propagate_render_layers_to_added_meshes(
+    mut commands: Commands,
+    vrms: Query<&RenderLayers, With<Vrm>>,
+    parents: Query<&ChildOf>,
+    added_meshes: Query<(Entity, MeshRenderLayers), Added<Mesh3d>>,
+) {
+    for (mesh, mesh_layers) in added_meshes.iter() {
+        if !is_inherited(mesh_layers) {
+            continue;
+        }
+        if let Some(layers) = parents
+            .iter_ancestors(mesh)
+            .find_map(|entity| vrms.get(entity).ok())
+        {
+            commands
+                .entity(mesh)
+                .insert((layers.clone(), InheritedVrmRenderLayers(layers.clone())));
+        }
+    }
+}
+
+fn remove_render_layers(
+    mut commands: Commands,
+    mut removed: RemovedComponents<RenderLayers>,
+    vrms: Query<(), (With<Vrm>, Without<RenderLayers>)>,
+    childrens: Query<&Children>,
+    meshes: Query<MeshRenderLayers, (With<Mesh3d>, With<InheritedVrmRenderLayers>)>,
+) {
+    for vrm in removed.read() {
+        if !vrms.contains(vrm) {
+            continue;
+        }
+        for entity in childrens.iter_descendants(vrm) {
+            let Ok(mesh_layers) = meshes.get(entity) else {
+                continue;
+            };
+            let mut entity = commands.entity(entity);
+            entity.remove::<InheritedVrmRenderLayers>();
+            if is_inherited(mesh_layers) {
+                entity.remove::<RenderLayers>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::test_app;
+    use crate::vrm::render_layers::VrmRenderLayersPlugin;
+    use bevy::prelude::*;
+    use bevy::render::view::RenderLayers;
+
+    #[test]
+    fn propagate_render_layers_to_meshes() {
+        let mut app = test_app();
+        app.add_plugins(VrmRenderLayersPlugin);
+        let vrm = app.world_mut().spawn((Vrm, RenderLayers::layer(1))).id();
+        let mesh = app
+            .world_mut()
+            .spawn((Mesh3d::default(), ChildOf(vrm)))
+            .id();
+        app.update();
+        assert_eq!(
+            app.world().get::<RenderLayers>(mesh),
+            Some(&RenderLayers::layer(1))
+        );
+
+        app.world_mut()
+            .entity_mut(vrm)
+            .insert(RenderLayers::layer(2));
+        app.update();
+        assert_eq!(
+            app.world().get::<RenderLayers>(mesh),
+            Some(&RenderLayers::layer(2))
+        );
+
+        app.world_mut().entity_mut(vrm).remove::<RenderLayers>();
+        app.update();
+        assert!(app.world().get::<RenderLayers>(mesh).is_none());
+    }
+
+    #[test]
+    fn keep_render_layers_of_mesh() {
+        let mut app = test_app();
+        app.add_plugins(VrmRenderLayersPlugin);
+        let vrm = app.world_mut().spawn((Vrm, RenderLayers::layer(1))).id();
+        let head = app
+            .world_mut()
+            .spawn((Mesh3d::default(), RenderLayers::layer(5), ChildOf(vrm)))
+            .id();
+        let body = app
+            .world_mut()
+            .spawn((Mesh3d::default(), ChildOf(vrm)))
+            .id();
+        app.update();
+        assert_eq!(
+            app.world().get::<RenderLayers>(head),
+            Some(&RenderLayers::layer(5))
+        );
+
+        // The layers changed from the outside after the propagation are also kept.
+        app.world_mut()
+            .entity_mut(body)
+            .insert(RenderLayers::layer(3));
+        app.world_mut()
+            .entity_mut(vrm)
+            .insert(RenderLayers::layer(2));
+        app.update();
+        assert_eq!(
+            app.world().get::<RenderLayers>(head),
+            Some(&RenderLayers::layer(5))
+        );
+        assert_eq!(
+            app.world().get::<RenderLayers>(body),
+            Some(&RenderLayers::layer(3))
+        );
+
+        app.world_mut().entity_mut(vrm).remove::<RenderLayers>();
+        app.update();
+        assert_eq!(
+            app.world().get::<RenderLayers>(head),
+            Some(&RenderLayers::layer(5))
+        );
+        assert_eq!(
+            app.world().get::<RenderLayers>(body),
+            Some(&RenderLayers::layer(3))
+        );
+    }
+}
@@ -1,10 +1,13 @@
 pub(crate) mod initialize;
 pub mod registry;
+pub mod self_collision;
 mod update;
 
-use crate::prelude::ColliderShape;
+use crate::macros::entity_component;
+use crate::prelude::{ColliderShape, SpringJointLimit};
 use crate::vrm::spring_bone::initialize::SpringBoneInitializePlugin;
 use crate::vrm::spring_bone::registry::SpringBoneRegistryPlugin;
+use crate::vrm::spring_bone::self_collision::SpringSelfCollision;
 use crate::vrm::spring_bone::update::SpringBoneUpdatePlugin;
 use bevy::app::App;
 use bevy::math::{Mat4, Quat, Vec3};
@@ -24,6 +27,25 @@ pub(crate) struct SpringJointState {
     bone_length: f32,
     initial_local_matrix: Mat4,
     initial_local_rotation: Quat,
+    /// The head position in the last update, in the same space as the tails.
+    prev_head: Vec3,
+    /// The bone length in world space in the last update.
+    /// `0.0` until the first update.
+    prev_world_bone_length: f32,
+}
+
+impl SpringJointState {
+    /// Returns the bone length in world space, taking the scale of the hierarchy into account.
+    #[inline]
+    fn world_bone_length(
+        &self,
+        head_gtf: &GlobalTransform,
+    ) -> f32 {
+        head_gtf
+            .affine()
+            .transform_vector3(self.bone_axis * self.bone_length)
+            .length()
+    }
 }
 
 #[derive(Component, Debug, Clone, PartialEq, Default, Reflect)]
@@ -31,6 +53,9 @@ pub(crate) struct SpringJointState {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
 pub(crate) struct SpringRoot {
+    /// The name of the spring.
+    pub name: String,
+
     /// Represents a list of entity of spring joints belonging to the spring chain.
     /// This component is inserted into the root entity of the chain.
     pub joints: SpringJoints,
@@ -42,6 +67,13 @@ pub(crate) struct SpringRoot {
     pub center_node: SpringCenterNode,
 }
 
+entity_component!(
+    /// Holds the entity of VRM to which the spring chain belongs.
+    ///
+    /// This component is inserted into the root entity of the chain.
+    SpringVrmEntity
+);
+
 #[derive(Eq, PartialEq, Debug, Clone, Default, Deref, Reflect)]
 #[reflect(Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -84,6 +116,9 @@ impl Plugin for VrmSpringBonePlugin {
             .register_type::<SpringColliders>()
             .register_type::<SpringCenterNode>()
             .register_type::<SpringJointProps>()
+            .register_type::<SpringJointLimit>()
+            .register_type::<SpringVrmEntity>()
+            .register_type::<SpringSelfCollision>()
             .add_plugins((
                 SpringBoneInitializePlugin,
                 SpringBoneRegistryPlugin,
@@ -1,10 +1,10 @@
 use crate::prelude::ChildSearcher;
 use crate::vrm::humanoid_bone::RequestInitializeHumanoidBones;
 use crate::vrm::spring_bone::registry::{
-    SpringColliderRegistry, SpringJointPropsRegistry, SpringNodeRegistry,
+    SpringColliderRegistry, SpringJointLimitRegistry, SpringJointPropsRegistry, SpringNodeRegistry,
 };
 use crate::vrm::spring_bone::{
-    SpringCenterNode, SpringColliders, SpringJointState, SpringJoints, SpringRoot,
+    SpringCenterNode, SpringColliders, SpringJointState, SpringJoints, SpringRoot, SpringVrmEntity,
 };
 use bevy::app::{App, Update};
 use bevy::prelude::*;
@@ -21,6 +21,7 @@ impl Plugin for SpringBoneInitializePlugin {
     ) {
         app.add_systems(Update, init_spring_joint_states)
             .add_observer(apply_initialize_joint_props)
+            .add_observer(apply_initialize_joint_limits)
             .add_observer(apply_initialize_collider_shapes)
             .add_observer(apply_initialize_spring_roots);
     }
@@ -44,6 +45,24 @@ fn apply_initialize_joint_props(
     }
 }
 
+fn apply_initialize_joint_limits(
+    trigger: Trigger<RequestInitializeHumanoidBones>,
+    mut commands: Commands,
+    child_searcher: ChildSearcher,
+    models: Query<&SpringJointLimitRegistry>,
+) {
+    let root = trigger.target();
+    let Ok(registry) = models.get(root) else {
+        return;
+    };
+    for (name, limit) in registry.iter() {
+        let Some(joint_entity) = child_searcher.find_from_name(root, name.as_str()) else {
+            continue;
+        };
+        commands.entity(joint_entity).insert(*limit);
+    }
+}
+
 fn apply_initialize_collider_shapes(
     trigger: Trigger<RequestInitializeSpringBone>,
     mut commands: Commands,
@@ -73,6 +92,7 @@ fn apply_initialize_spring_roots(
         return;
     };
     for spring_root in registry.0.iter().map(|spring| SpringRoot {
+        name: spring.name.clone(),
         center_node: SpringCenterNode(
             spring
                 .center
@@ -100,7 +120,9 @@ fn apply_initialize_spring_roots(
         let Some(root) = spring_root.joints.first() else {
             continue;
         };
-        commands.entity(*root).insert(spring_root);
+        commands
+            .entity(*root)
+            .insert((spring_root, SpringVrmEntity(entity)));
     }
 }
 
@@ -135,6 +157,8 @@ fn init_spring_joint_states(
                 bone_length: tail_tf.translation.length(),
                 initial_local_matrix: head_tf.compute_matrix(),
                 initial_local_rotation: head_tf.rotation,
+                prev_head: Vec3::ZERO,
+                prev_world_bone_length: 0.,
             };
             par_commands.command_scope(|mut commands| {
                 commands.entity(head_entity).insert(state);
@@ -1,5 +1,5 @@
 use crate::vrm::gltf::extensions::vrmc_spring_bone::{
-    Collider, ColliderShape, Spring, SpringJoint, VRMCSpringBone,
+    Collider, ColliderShape, Spring, SpringJoint, SpringJointLimit, VRMCSpringBone,
 };
 use crate::vrm::spring_bone::SpringJointProps;
 use bevy::app::App;
@@ -18,6 +18,7 @@ impl Plugin for SpringBoneRegistryPlugin {
     ) {
         app.register_type::<SpringColliderRegistry>()
             .register_type::<SpringJointPropsRegistry>()
+            .register_type::<SpringJointLimitRegistry>()
             .register_type::<SpringNodeRegistry>();
     }
 }
@@ -82,11 +83,37 @@ impl SpringJointPropsRegistry {
     }
 }
 
+#[derive(Component, Deref, Debug, Default, Clone, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub(crate) struct SpringJointLimitRegistry(pub(crate) HashMap<Name, SpringJointLimit>);
+
+impl SpringJointLimitRegistry {
+    pub fn new(
+        joints: &[SpringJoint],
+        node_assets: &Assets<GltfNode>,
+        nodes: &[Handle<GltfNode>],
+    ) -> Self {
+        Self(
+            joints
+                .iter()
+                .filter_map(|joint| {
+                    let limit = joint.extensions?.limit?;
+                    let name = get_node_name(joint.node, node_assets, nodes)?;
+                    Some((name, limit))
+                })
+                .collect(),
+        )
+    }
+}
+
 #[derive(Component, Debug, Default, Clone, PartialEq, Reflect)]
 #[reflect(Component, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
 pub(crate) struct SpringNode {
+    pub name: String,
     pub center: Option<Name>,
     pub joints: Vec<Name>,
     pub colliders: Vec<(Name, ColliderShape)>,
@@ -109,6 +136,7 @@ impl SpringNodeRegistry {
                 .springs
                 .iter()
                 .map(|spring| SpringNode {
+                    name: spring.name.clone(),
                     joints: spring
                         .joints
                         .iter()
@@ -0,0 +1,180 @@
+use crate::prelude::{ColliderShape, Sphere};
+use crate::vrm::spring_bone::{SpringRoot, SpringVrmEntity};
+use bevy::prelude::*;
+
+/// Enables collisions between the spring chains within the same VRM.
+///
+/// When this component is inserted into the entity of VRM,
+/// the joints of the selected springs act as sphere colliders whose radius is the hit radius of each joint,
+/// and the joints of the other springs in the VRM are pushed out of them.
+///
+/// This is useful to prevent twin-tails or multi-layer skirts from intersecting each other.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn spawn_vrm(
+///     mut commands: Commands,
+///     asset_server: Res<AssetServer>,
+/// ){
+///     commands.spawn((
+///         VrmHandle(asset_server.load("<vrm>.vrm")),
+///         SpringSelfCollision::new(["TwinTail_L", "TwinTail_R"]),
+///     ));
+/// }
+/// ```
+#[derive(Component, Debug, Clone, Default, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct SpringSelfCollision {
+    /// The names of the springs whose joints act as colliders.
+    ///
+    /// If empty, the joints of all springs act as colliders.
+    pub springs: Vec<String>,
+}
+
+impl SpringSelfCollision {
+    /// Creates a new [`SpringSelfCollision`] that selects the springs with the given names.
+    pub fn new(springs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            springs: springs.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Creates a new [`SpringSelfCollision`] that selects all springs.
+    #[inline]
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn is_selected(
+        &self,
+        spring_name: &str,
+    ) -> bool {
+        self.springs.is_empty() || self.springs.iter().any(|name| name == spring_name)
+    }
+}
+
+/// The joint spheres of a spring chain captured at the beginning of the frame.
+pub(crate) struct SelfColliderChain {
+    vrm: Entity,
+    root: Entity,
+    spheres: Vec<(Vec3, f32)>,
+    min: Vec3,
+    max: Vec3,
+}
+
+impl SelfColliderChain {
+    /// Returns whether the sphere may intersect this chain.
+    ///
+    /// This is the broadphase that skips the chains far from the joint.
+    #[inline]
+    fn may_intersect(
+        &self,
+        center: Vec3,
+        radius: f32,
+    ) -> bool {
+        let closest = center.clamp(self.min, self.max);
+        closest.distance_squared(center) <= radius * radius
+    }
+}
+
+pub(crate) fn collect_self_collider_chains(
+    spring_roots: &Query<(Entity, &SpringRoot, Option<&SpringVrmEntity>)>,
+    self_collisions: &Query<&SpringSelfCollision>,
+    joint_sphere: impl Fn(Entity) -> Option<(Vec3, f32)>,
+) -> Vec<SelfColliderChain> {
+    spring_roots
+        .iter()
+        .filter_map(|(root, spring_root, vrm)| {
+            let vrm = vrm?.0;
+            if !self_collisions
+                .get(vrm)
+                .ok()?
+                .is_selected(&spring_root.name)
+            {
+                return None;
+            }
+            let spheres = spring_root
+                .joints
+                .iter()
+                .filter_map(|joint| joint_sphere(*joint))
+                .collect::<Vec<_>>();
+            let (min, max) = spheres.iter().fold(
+                (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                |(min, max), (center, radius)| {
+                    (min.min(center - *radius), max.max(center + *radius))
+                },
+            );
+            Some(SelfColliderChain {
+                vrm,
+                root,
+                spheres,
+                min,
+                max,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn apply_self_collision(
+    next_tail: &mut Vec3,
+    chains: &[SelfColliderChain],
+    vrm: Entity,
+    root: Entity,
+    joint_radius: f32,
+    head_global_pos: Vec3,
+    bone_length: f32,
+) {
+    for chain in chains
+        .iter()
+        .filter(|chain| chain.vrm == vrm && chain.root != root)
+    {
+        if !chain.may_intersect(*next_tail, joint_radius) {
+            continue;
+        }
+        for (center, radius) in chain.spheres.iter() {
+            let shape = ColliderShape::Sphere(Sphere {
+                offset: [0.; 3],
+                radius: *radius,
+            });
+            shape.apply_collision(
+                next_tail,
+                &GlobalTransform::from_translation(*center),
+                head_global_pos,
+                joint_radius,
+                bone_length,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vrm::spring_bone::self_collision::{SelfColliderChain, SpringSelfCollision};
+    use bevy::prelude::*;
+
+    #[test]
+    fn select_springs() {
+        assert!(SpringSelfCollision::all().is_selected("Hair"));
+        let self_collision = SpringSelfCollision::new(["TwinTail_L", "TwinTail_R"]);
+        assert!(self_collision.is_selected("TwinTail_L"));
+        assert!(!self_collision.is_selected("Skirt"));
+    }
+
+    #[test]
+    fn broadphase_skips_far_chains() {
+        let chain = SelfColliderChain {
+            vrm: Entity::PLACEHOLDER,
+            root: Entity::PLACEHOLDER,
+            spheres: vec![(Vec3::ZERO, 0.1)],
+            min: Vec3::splat(-0.1),
+            max: Vec3::splat(0.1),
+        };
+        assert!(chain.may_intersect(Vec3::new(0.15, 0., 0.), 0.1));
+        assert!(!chain.may_intersect(Vec3::new(1., 0., 0.), 0.1));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::system_set::VrmSystemSets;
-use crate::vrm::gltf::extensions::vrmc_spring_bone::ColliderShape;
-use crate::vrm::spring_bone::{SpringJointProps, SpringJointState, SpringRoot};
+use crate::vrm::gltf::extensions::vrmc_spring_bone::{ColliderShape, SpringJointLimit};
+use crate::vrm::spring_bone::self_collision::{
+    SpringSelfCollision, apply_self_collision, collect_self_collider_chains,
+};
+use crate::vrm::spring_bone::{SpringJointProps, SpringJointState, SpringRoot, SpringVrmEntity};
 use bevy::app::{Animation, App};
 use bevy::math::Vec3;
 use bevy::prelude::TransformSystem::TransformPropagate;
@@ -26,19 +29,32 @@ impl Plugin for SpringBoneUpdatePlugin {
 
 fn update_spring_bones(
     mut transforms: Query<(&mut Transform, &mut GlobalTransform)>,
-    mut joints: Query<(&ChildOf, &mut SpringJointState, &SpringJointProps)>,
-    spring_roots: Query<&SpringRoot>,
+    mut joints: Query<(
+        &ChildOf,
+        &mut SpringJointState,
+        &SpringJointProps,
+        Option<&SpringJointLimit>,
+    )>,
+    spring_roots: Query<(Entity, &SpringRoot, Option<&SpringVrmEntity>)>,
+    self_collisions: Query<&SpringSelfCollision>,
     time: Res<Time>,
 ) {
     let delta_time = time.delta_secs();
-    for spring_root in spring_roots.iter() {
+    let self_collider_chains =
+        collect_self_collider_chains(&spring_roots, &self_collisions, |joint| {
+            let (_, _, props, _) = joints.get(joint).ok()?;
+            let (_, gtf) = transforms.get(joint).ok()?;
+            let scale = gtf.scale().abs().max_element();
+            Some((gtf.translation(), props.hit_radius * scale))
+        });
+    for (root, spring_root, vrm) in spring_roots.iter() {
         let center_gtf = spring_root
             .center_node
             .and_then(|center| transforms.get(center).ok())
             .map(|(_, gtf)| gtf)
             .copied();
         for joint in spring_root.joints.iter().copied() {
-            let Ok((child_of, mut state, props)) = joints.get_mut(joint) else {
+            let Ok((child_of, mut state, props, limit)) = joints.get_mut(joint) else {
                 continue;
             };
             let parent_gtf = transforms
@@ -46,11 +62,18 @@ fn update_spring_bones(
                 .map(|(_, gtf)| *gtf)
                 .unwrap_or_default();
             let parent_global_rotation = parent_gtf.to_scale_rotation_translation().1;
-            let Ok(head_global_pos) = transforms.get(joint).map(|(_, gtf)| gtf.translation())
-            else {
+            let Ok(head_gtf) = transforms.get(joint).map(|(_, gtf)| *gtf) else {
                 continue;
             };
+            let head_global_pos = head_gtf.translation();
+            let scale = head_gtf.scale().abs().max_element();
+            let bone_length = state.world_bone_length(&head_gtf);
 
+            if 0. < state.prev_world_bone_length
+                && 1e-4 < (bone_length / state.prev_world_bone_length - 1.).abs()
+            {
+                rescale_tails(&mut state, head_global_pos, bone_length, &center_gtf);
+            }
             let current_tail = center_local_to_global(state.current_tail, &center_gtf);
             let prev_tail = center_local_to_global(state.prev_tail, &center_gtf);
             let inertia = (current_tail - prev_tail) * (1. - props.drag_force);
@@ -58,29 +81,49 @@ fn update_spring_bones(
                 * (parent_global_rotation
                     * state.initial_local_rotation
                     * state.bone_axis
-                    * props.stiffness);
-            let external = delta_time * props.gravity_dir * props.gravity_power;
+                    * props.stiffness
+                    * scale);
+            let external = delta_time * props.gravity_dir * props.gravity_power * scale;
 
             let next_tail = current_tail + inertia + stiffness + external;
             let mut next_tail =
-                head_global_pos + (next_tail - head_global_pos).normalize() * state.bone_length;
+                head_global_pos + (next_tail - head_global_pos).normalize() * bone_length;
 
             apply_collision(
                 &mut next_tail,
                 spring_root.colliders.iter().copied(),
-                props.hit_radius,
+                props.hit_radius * scale,
                 head_global_pos,
-                state.bone_length,
+                bone_length,
                 &transforms,
             );
+            if let Some(vrm) = vrm {
+                apply_self_collision(
+                    &mut next_tail,
+                    &self_collider_chains,
+                    vrm.0,
+                    root,
+                    props.hit_radius * scale,
+                    head_global_pos,
+                    bone_length,
+                );
+            }
 
-            state.prev_tail = state.current_tail;
-            state.current_tail = global_to_center_local(next_tail, &center_gtf);
-
-            let to = (parent_gtf.compute_matrix() * state.initial_local_matrix)
+            let rest_matrix = parent_gtf.compute_matrix() * state.initial_local_matrix;
+            let mut to = rest_matrix
                 .inverse()
                 .transform_point3(next_tail)
                 .normalize();
+            if let Some(limit) = limit {
+                to = limit.clamp(state.bone_axis, to);
+                next_tail =
+                    head_global_pos + rest_matrix.transform_vector3(to).normalize() * bone_length;
+            }
+
+            state.prev_tail = state.current_tail;
+            state.current_tail = global_to_center_local(next_tail, &center_gtf);
+            state.prev_head = global_to_center_local(head_global_pos, &center_gtf);
+            state.prev_world_bone_length = bone_length;
 
             let Ok((mut tf, mut gtf)) = transforms.get_mut(joint) else {
                 continue;
@@ -93,6 +136,29 @@ fn update_spring_bones(
     }
 }
 
+/// Rescales the tails around the head when the scale of the hierarchy has changed.
+///
+/// The tails are still in the previous scale, so they would cause the inertia without this.
+fn rescale_tails(
+    state: &mut SpringJointState,
+    head_global_pos: Vec3,
+    bone_length: f32,
+    center_gtf: &Option<GlobalTransform>,
+) {
+    let prev_head = center_local_to_global(state.prev_head, center_gtf);
+    let current_tail = center_local_to_global(state.current_tail, center_gtf);
+    let prev_tail = center_local_to_global(state.prev_tail, center_gtf);
+    let ratio = bone_length / current_tail.distance(prev_head).max(f32::EPSILON);
+    state.current_tail = global_to_center_local(
+        head_global_pos + (current_tail - prev_head) * ratio,
+        center_gtf,
+    );
+    state.prev_tail = global_to_center_local(
+        head_global_pos + (prev_tail - prev_head) * ratio,
+        center_gtf,
+    );
+}
+
 fn center_local_to_global(
     tail_pos: Vec3,
     center_gtf: &Option<GlobalTransform>,
@@ -136,3 +202,117 @@ fn apply_collision(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_app;
+    use crate::vrm::spring_bone::initialize::SpringBoneInitializePlugin;
+    use crate::vrm::spring_bone::update::SpringBoneUpdatePlugin;
+    use crate::vrm::spring_bone::{
+        SpringCenterNode, SpringColliders, SpringJointProps, SpringJointState, SpringJoints,
+        SpringRoot,
+    };
+    use bevy::prelude::*;
+
+    #[test]
+    fn bone_length_follows_hierarchy_scale() {
+        let mut app = test_app();
+        app.add_plugins((
+            TransformPlugin,
+            SpringBoneInitializePlugin,
+            SpringBoneUpdatePlugin,
+        ));
+        let vrm = app
+            .world_mut()
+            .spawn(Transform::from_scale(Vec3::splat(2.)))
+            .id();
+        let head = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(0., 1., 0.),
+                SpringJointProps {
+                    gravity_dir: Vec3::NEG_Y,
+                    gravity_power: 1.,
+                    ..default()
+                },
+                ChildOf(vrm),
+            ))
+            .id();
+        let tail = app
+            .world_mut()
+            .spawn((Transform::from_xyz(0., 0., 0.5), ChildOf(head)))
+            .id();
+        app.update();
+        app.world_mut().entity_mut(head).insert(SpringRoot {
+            name: "Hair".to_string(),
+            joints: SpringJoints(vec![head, tail]),
+            colliders: SpringColliders::default(),
+            center_node: SpringCenterNode::default(),
+        });
+        app.update();
+        app.update();
+
+        let assert_bone_length = |app: &mut App, expected: f32| {
+            let world = app.world_mut();
+            let head_pos = world.get::<GlobalTransform>(head).unwrap().translation();
+            let state = world.get::<SpringJointState>(head).unwrap();
+            let length = state.current_tail.distance(head_pos);
+            assert!(
+                (length - expected).abs() < 1e-4,
+                "expected {expected}, got {length}"
+            );
+        };
+        assert_bone_length(&mut app, 1.);
+
+        app.world_mut().get_mut::<Transform>(vrm).unwrap().scale = Vec3::splat(0.5);
+        app.update();
+        app.update();
+        assert_bone_length(&mut app, 0.25);
+    }
+
+    #[test]
+    fn keep_tail_still_after_scale_change() {
+        let mut app = test_app();
+        app.add_plugins((
+            TransformPlugin,
+            SpringBoneInitializePlugin,
+            SpringBoneUpdatePlugin,
+        ));
+        let vrm = app
+            .world_mut()
+            .spawn(Transform::from_scale(Vec3::splat(2.)))
+            .id();
+        let head = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(0., 1., 0.),
+                SpringJointProps::default(),
+                ChildOf(vrm),
+            ))
+            .id();
+        let tail = app
+            .world_mut()
+            .spawn((Transform::from_xyz(0., 0., 0.5), ChildOf(head)))
+            .id();
+        app.update();
+        app.world_mut().entity_mut(head).insert(SpringRoot {
+            name: "Hair".to_string(),
+            joints: SpringJoints(vec![head, tail]),
+            colliders: SpringColliders::default(),
+            center_node: SpringCenterNode::default(),
+        });
+        app.update();
+        app.update();
+
+        app.world_mut().get_mut::<Transform>(vrm).unwrap().scale = Vec3::splat(0.5);
+        app.update();
+        app.update();
+
+        let world = app.world_mut();
+        let state = world.get::<SpringJointState>(head).unwrap();
+        let velocity = state.current_tail - state.prev_tail;
+        assert!(velocity.length() < 1e-4, "{velocity}");
+        let rotation = world.get::<Transform>(head).unwrap().rotation;
+        assert!(rotation.abs_diff_eq(Quat::IDENTITY, 1e-4), "{rotation}");
+    }
+}
@@ -5,6 +5,7 @@ mod loader;
 
 use crate::macros::{entity_component, marker_component};
 use crate::vrma::animation::VrmaAnimationPlayersPlugin;
+use crate::vrma::animation::markers::VrmaMarker;
 use crate::vrma::initialize::VrmaInitializePlugin;
 use crate::vrma::loader::{VrmaAsset, VrmaLoaderPlugin};
 use bevy::app::App;
@@ -15,8 +16,8 @@ use std::time::Duration;
 
 pub mod prelude {
     pub use crate::vrma::{
-        LoadedVrma, Vrma, VrmaDuration, VrmaEntity, VrmaHandle, VrmaPath, VrmaPlugin,
-        animation::prelude::*, loader::VrmaAsset,
+        LoadedVrma, Vrma, VrmaClip, VrmaClipSelector, VrmaClips, VrmaDuration, VrmaEntity,
+        VrmaHandle, VrmaPath, VrmaPlugin, animation::prelude::*, loader::VrmaAsset,
     };
 }
 
@@ -40,9 +41,12 @@ impl Plugin for VrmaPlugin {
             .register_type::<VrmaHandle>()
             .register_type::<VrmaPath>()
             .register_type::<VrmaDuration>()
+            .register_type::<VrmaClips>()
+            .register_type::<VrmaClip>()
+            .register_type::<VrmaClipSelector>()
             .register_type::<RetargetSource>()
-            .register_type::<VrmAnimationClipHandle>()
-            .register_type::<VrmAnimationNodeIndex>();
+            .register_type::<VrmAnimationClipHandles>()
+            .register_type::<VrmAnimationNodeIndices>();
     }
 }
 
@@ -53,6 +57,7 @@ impl Plugin for VrmaPlugin {
 /// - [`Vrma`]
 /// - [`VrmaPath`]
 /// - [`VrmaDuration`]
+/// - [`VrmaClips`]
 /// - [`BoneRestTransform`](crate::prelude::BoneRestTransform)
 /// - [`BoneRestGlobalTransform`](crate::prelude::BoneRestGlobalTransform)
 /// - [`SceneRoot`](bevy::scene::SceneRoot)
@@ -83,16 +88,93 @@ entity_component!(
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
 pub struct VrmaPath(pub PathBuf);
-/// The component that holds the duration of VRMA's animation.
+/// The component that holds the duration of the longest animation in VRMA.
 /// This component is automatically attached to the entity with the same entity as [`VrmaHandle`] after loading VRMA.
 ///
-/// This component's structure will be changed in the future if VRMA can have multiple animations.
+/// The duration of each animation is held in [`VrmaClips`].
 #[derive(Debug, Component, Reflect)]
 #[reflect(Component)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
 pub struct VrmaDuration(pub Duration);
 
+/// The component that holds the animations of VRMA in the order of the glTF file.
+/// This component is automatically attached to the entity with the same entity as [`VrmaHandle`] after loading VRMA.
+///
+/// Each animation can be played by specifying [`PlayVrma::clip`](crate::prelude::PlayVrma::clip).
+#[derive(Debug, Component, Clone, Default, Reflect, Deref)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaClips(pub Vec<VrmaClip>);
+
+impl VrmaClips {
+    /// Returns the index of the clip specified by the selector.
+    pub fn index_of(
+        &self,
+        selector: &VrmaClipSelector,
+    ) -> Option<usize> {
+        match selector {
+            VrmaClipSelector::Index(index) => (*index < self.0.len()).then_some(*index),
+            VrmaClipSelector::Name(name) => self
+                .0
+                .iter()
+                .position(|clip| clip.name.as_deref() == Some(name.as_str())),
+        }
+    }
+}
+
+/// An animation in VRMA.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaClip {
+    /// The name of the animation in the glTF file.
+    pub name: Option<String>,
+    /// The duration of the animation.
+    pub duration: Duration,
+    /// The timed markers fired during playback.
+    pub markers: Vec<VrmaMarker>,
+}
+
+/// Specifies the animation in VRMA.
+///
+/// Default is the first animation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub enum VrmaClipSelector {
+    /// The index of the animation in the glTF file.
+    Index(usize),
+    /// The name of the animation in the glTF file.
+    Name(String),
+}
+
+impl Default for VrmaClipSelector {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+impl From<usize> for VrmaClipSelector {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl From<&str> for VrmaClipSelector {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+impl From<String> for VrmaClipSelector {
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
 /// An event that is emitted when VRMA is loaded.
 ///
 /// This event is emitted as a trigger.
@@ -102,15 +184,15 @@ pub struct LoadedVrma {
     pub vrm: Entity,
 }
 
-/// The component that holds the animation clip of VRMA.
+/// The component that holds the animation clips of VRMA in the same order as [`VrmaClips`].
 #[derive(Debug, Component, Reflect)]
 #[reflect(Component)]
-pub(crate) struct VrmAnimationClipHandle(pub Handle<AnimationClip>);
+pub(crate) struct VrmAnimationClipHandles(pub Vec<Handle<AnimationClip>>);
 
-/// The component that holds the animation node index for VRMA.
-#[derive(Debug, Component, Reflect, Copy, Clone, Default)]
+/// The component that holds the animation node indices for the clips of VRMA.
+#[derive(Debug, Component, Reflect, Clone, Default, Deref)]
 #[reflect(Component, Default)]
-pub(crate) struct VrmAnimationNodeIndex(pub AnimationNodeIndex);
+pub(crate) struct VrmAnimationNodeIndices(pub Vec<AnimationNodeIndex>);
 
 /// This is a component that indicates that it is the source of retargeting.
 /// This is used internally to retarget bones and expressions, and attached after vrma's entity children are spawned.
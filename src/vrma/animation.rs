@@ -1,14 +1,27 @@
 pub(crate) mod animation_graph;
+mod blend_space;
 mod bone_rotation;
 mod bone_translation;
+mod control;
 pub(crate) mod expressions;
+mod layer;
+pub(crate) mod markers;
 mod play;
+mod playback_events;
+mod retarget;
+mod state_machine;
 
-use crate::prelude::VrmSystemSets;
+use crate::prelude::{ChildSearcher, VrmSystemSets};
 use crate::vrma::RetargetSource;
 use crate::vrma::animation::animation_graph::VrmaAnimationGraphPlugin;
+use crate::vrma::animation::blend_space::VrmaBlendSpacePlugin;
+use crate::vrma::animation::control::VrmaAnimationControlPlugin;
 use crate::vrma::animation::expressions::VrmaRetargetExpressionsPlugin;
+use crate::vrma::animation::layer::VrmaLayerPlugin;
+use crate::vrma::animation::markers::VrmaMarkersPlugin;
 use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+use crate::vrma::animation::playback_events::VrmaPlaybackEventsPlugin;
+use crate::vrma::animation::state_machine::VrmaStateMachinePlugin;
 use bevy::app::App;
 use bevy::prelude::*;
 use bevy::window::RequestRedraw;
@@ -16,7 +29,17 @@ use bevy::window::RequestRedraw;
 pub mod prelude {
     pub use crate::vrma::animation::{
         VrmaAnimationPlayers,
+        blend_space::{VrmaBlendSample, VrmaBlendSpace},
+        control::{PauseVrma, ResumeVrma, SeekVrma, SetVrmaSpeed},
+        layer::{HumanoidGroup, PlayVrmaLayer, VrmaLayer, VrmaLayerMode},
+        markers::{AddVrmaMarker, VrmaMarker, VrmaMarkerReached},
         play::{PlayVrma, StopVrma},
+        playback_events::{VrmaFinished, VrmaLooped},
+        state_machine::{
+            VrmaCondition, VrmaMotion, VrmaMotionSample, VrmaState, VrmaStateMachine,
+            VrmaStateMachineHandle, VrmaStateMachineParameters, VrmaStateMachineState,
+            VrmaStateTransition,
+        },
     };
 }
 
@@ -31,6 +54,12 @@ impl Plugin for VrmaAnimationPlayersPlugin {
             .add_plugins((
                 VrmaAnimationGraphPlugin,
                 VrmaAnimationPlayPlugin,
+                VrmaAnimationControlPlugin,
+                VrmaPlaybackEventsPlugin,
+                VrmaMarkersPlugin,
+                VrmaLayerPlugin,
+                VrmaBlendSpacePlugin,
+                VrmaStateMachinePlugin,
                 VrmaRetargetExpressionsPlugin,
             ))
             .add_systems(
@@ -50,6 +79,25 @@ impl Plugin for VrmaAnimationPlayersPlugin {
 #[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
 pub struct VrmaAnimationPlayers(pub Vec<Entity>);
 
+/// Returns the entities of the animation players that play VRMA on the VRM:
+/// the root bone and each expression.
+fn vrm_animation_players(
+    vrm: Entity,
+    searcher: &ChildSearcher,
+    childrens: &Query<&Children>,
+) -> Vec<Entity> {
+    let expressions = searcher
+        .find_expressions_root(vrm)
+        .and_then(|root| childrens.get(root).ok())
+        .into_iter()
+        .flat_map(|children| children.iter());
+    searcher
+        .find_root_bone(vrm)
+        .into_iter()
+        .chain(expressions)
+        .collect()
+}
+
 fn any_playing_animations(players: Query<&AnimationPlayer, With<RetargetSource>>) -> bool {
     players.iter().any(|p| !p.all_finished())
 }
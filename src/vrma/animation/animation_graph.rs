@@ -1,15 +1,20 @@
 use crate::prelude::{BoneRestGlobalTransform, BoneRestTransform, ChildSearcher};
+use crate::vrm::VrmBone;
 use crate::vrm::expressions::VrmExpressionRegistry;
 use crate::vrm::humanoid_bone::HumanoidBoneRegistry;
 use crate::vrma::animation::bone_rotation::{
-    BoneRotationAnimationCurve, register_rotate_transformation,
+    self, BoneRotationAnimationCurve, register_rotate_transformation,
 };
 use crate::vrma::animation::bone_translation::{
-    HipsTranslationAnimationCurve, register_hips_translation_transformation,
+    self, HipsTranslationAnimationCurve, register_hips_translation_transformation,
 };
-use crate::vrma::{VrmAnimationClipHandle, VrmAnimationNodeIndex};
+use crate::vrma::animation::layer::assign_mask_groups;
+use crate::vrma::animation::retarget::{RetargetTransformation, RetargetTransformations};
+use crate::vrma::{VrmAnimationClipHandles, VrmAnimationNodeIndices};
+use bevy::animation::graph::AnimationNodeType;
 use bevy::animation::{AnimationTarget, animated_field};
 use bevy::app::App;
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 
 #[derive(Event)]
@@ -19,7 +24,7 @@ pub(crate) struct RequestUpdateAnimationGraph {
 }
 
 #[derive(Event)]
-struct RequestUpdateAnimationClips;
+pub(crate) struct RequestUpdateAnimationClips;
 
 pub(super) struct VrmaAnimationGraphPlugin;
 
@@ -30,7 +35,8 @@ impl Plugin for VrmaAnimationGraphPlugin {
     ) {
         app.add_observer(apply_animation_graph)
             .add_observer(apply_replace_humanoid_bone_animation_clips)
-            .add_observer(apply_regenerate_expression_clips);
+            .add_observer(apply_regenerate_expression_clips)
+            .add_observer(remove_retarget_transformations);
     }
 }
 
@@ -39,16 +45,41 @@ fn apply_animation_graph(
     mut commands: Commands,
     mut graphs: ResMut<Assets<AnimationGraph>>,
     childrens: Query<&Children>,
-    vrmas: Query<(Entity, &VrmAnimationClipHandle)>,
+    vrmas: Query<(
+        Entity,
+        &VrmAnimationClipHandles,
+        Option<&VrmAnimationNodeIndices>,
+    )>,
     child_searcher: ChildSearcher,
     entities: Query<(Has<AnimationPlayer>, Option<&AnimationGraphHandle>)>,
+    bones: Query<(Option<&VrmBone>, &AnimationTarget)>,
+    mut rotations: Query<&mut RetargetTransformations<bone_rotation::Transformation>>,
+    mut translations: Query<&mut RetargetTransformations<bone_translation::Transformation>>,
 ) {
     let vrma_entity = trigger.vrma;
     let vrm_entity = trigger.vrm;
     let Ok(children) = childrens.get(vrm_entity) else {
         return;
     };
-    let animation_graph = generate_animation_graph(&mut commands, &vrmas, children);
+    let (mut animation_graph, moved_nodes) =
+        generate_animation_graph(&mut commands, &vrmas, children);
+    // The transformations of the new VRMA are registered by `RequestUpdateAnimationClips`,
+    // but the ones of the other VRMAs must follow their new nodes.
+    for bone in childrens.iter_descendants(vrm_entity) {
+        if let Ok(mut transformations) = rotations.get_mut(bone) {
+            remap_transformations(&mut transformations, &moved_nodes);
+        }
+        if let Ok(mut transformations) = translations.get_mut(bone) {
+            remap_transformations(&mut transformations, &moved_nodes);
+        }
+    }
+    assign_mask_groups(
+        &mut animation_graph,
+        vrm_entity,
+        &child_searcher,
+        &childrens,
+        &bones,
+    );
     let animation_graph_handle = AnimationGraphHandle(graphs.add(animation_graph));
     insert_animation_graph_into_root_bone(
         vrm_entity,
@@ -70,22 +101,77 @@ fn apply_animation_graph(
         .trigger(RequestUpdateAnimationClips);
 }
 
+/// Generates the animation graph from the VRMAs of VRM.
+///
+/// Returns the graph and the map from the previous nodes of the VRMAs to the new ones.
 fn generate_animation_graph(
     commands: &mut Commands,
-    vrmas_query: &Query<(Entity, &VrmAnimationClipHandle)>,
+    vrmas_query: &Query<(
+        Entity,
+        &VrmAnimationClipHandles,
+        Option<&VrmAnimationNodeIndices>,
+    )>,
     children: &Children,
-) -> AnimationGraph {
+) -> (
+    AnimationGraph,
+    HashMap<AnimationNodeIndex, AnimationNodeIndex>,
+) {
     let vrmas = children
         .iter()
         .flat_map(|child| vrmas_query.get(child).ok())
         .collect::<Vec<_>>();
-    let (graph, nodes) = AnimationGraph::from_clips(vrmas.iter().map(|(_, h)| h.0.clone()));
-    for (i, (entity, _)) in vrmas.iter().enumerate() {
+    let (graph, nodes) =
+        vrm_animation_graph(vrmas.iter().flat_map(|(_, h, _)| h.0.iter().cloned()));
+    let mut nodes = nodes.into_iter();
+    let mut moved_nodes = HashMap::default();
+    for (entity, handles, previous) in vrmas {
+        let new_nodes = nodes.by_ref().take(handles.0.len()).collect::<Vec<_>>();
+        if let Some(previous) = previous {
+            moved_nodes.extend(previous.iter().copied().zip(new_nodes.iter().copied()));
+        }
         commands
-            .entity(*entity)
-            .insert(VrmAnimationNodeIndex(nodes[i]));
+            .entity(entity)
+            .insert(VrmAnimationNodeIndices(new_nodes));
     }
-    graph
+    (graph, moved_nodes)
+}
+
+/// Moves the transformations to the new nodes, and drops the ones whose VRMA no longer exists.
+fn remap_transformations<T: RetargetTransformation>(
+    transformations: &mut RetargetTransformations<T>,
+    moved_nodes: &HashMap<AnimationNodeIndex, AnimationNodeIndex>,
+) {
+    transformations.0 = transformations
+        .drain()
+        .filter_map(|(node, transformation)| {
+            moved_nodes
+                .get(&node)
+                .map(|new_node| (*new_node, transformation))
+        })
+        .collect();
+}
+
+/// Creates the animation graph of VRM from the clips of VRMAs.
+///
+/// The root is an additive blend node, whose first child is the blend node of the base animations.
+/// The clips are placed under the base node, and moved under the root while played as additive layers.
+pub(crate) fn vrm_animation_graph(
+    clips: impl IntoIterator<Item = Handle<AnimationClip>>
+) -> (AnimationGraph, Vec<AnimationNodeIndex>) {
+    let mut graph = AnimationGraph::new();
+    let root = graph.root;
+    if let Some(root) = graph.get_mut(root) {
+        root.node_type = AnimationNodeType::Add;
+    }
+    // The base node must have the smallest index to be evaluated before the additive layers.
+    let base = graph.add_blend(1.0, root);
+    let nodes = graph.add_clips(clips, 1.0, base).collect();
+    (graph, nodes)
+}
+
+/// Returns the blend node of the base animations created by [`vrm_animation_graph`].
+pub(crate) fn base_node(graph: &AnimationGraph) -> Option<AnimationNodeIndex> {
+    graph.graph.neighbors(graph.root).min()
 }
 
 fn insert_animation_graph_into_root_bone(
@@ -132,8 +218,9 @@ fn insert_animation_graph_into_expressions(
 
 fn apply_replace_humanoid_bone_animation_clips(
     trigger: Trigger<RequestUpdateAnimationClips>,
+    mut commands: Commands,
     mut clips: ResMut<Assets<AnimationClip>>,
-    clip_handles: Query<&VrmAnimationClipHandle>,
+    clip_handles: Query<&VrmAnimationClipHandles>,
     parents: Query<&ChildOf>,
     vrms: Query<&HumanoidBoneRegistry>,
     bones: Query<(
@@ -141,48 +228,58 @@ fn apply_replace_humanoid_bone_animation_clips(
         &BoneRestGlobalTransform,
         &AnimationTarget,
     )>,
-    nodes: Query<&VrmAnimationNodeIndex>,
+    nodes: Query<&VrmAnimationNodeIndices>,
     searcher: ChildSearcher,
 ) {
     let vrma_entity = trigger.target();
     let Ok(ChildOf(vrm_entity)) = parents.get(vrma_entity) else {
         return;
     };
-    let Ok(vrma_node_index) = nodes.get(vrma_entity) else {
+    let Ok(vrma_node_indices) = nodes.get(vrma_entity) else {
         return;
     };
     let Ok(registry) = vrms.get(vrma_entity) else {
         return;
     };
-    let Ok(vrm_animation_clip_handle) = clip_handles.get(vrma_entity) else {
+    let Ok(vrm_animation_clip_handles) = clip_handles.get(vrma_entity) else {
         return;
     };
     let Some(root_bone) = searcher.find_root_bone(*vrm_entity) else {
         return;
     };
-    let Some(clip) = clips.get_mut(vrm_animation_clip_handle.0.id()) else {
-        return;
-    };
-    register_rotate_transformation(
-        vrma_entity,
-        vrma_node_index.0,
-        root_bone,
-        registry,
-        &searcher,
-        &bones,
-    );
-    replace_bone_animation_clips(
-        clip,
-        vrma_node_index.0,
-        vrma_entity,
-        root_bone,
-        registry,
-        &searcher,
-        &bones,
-    );
+    // Each clip has its own node, but all of them share the rest pose of VRMA.
+    for (handle, node_index) in vrm_animation_clip_handles
+        .0
+        .iter()
+        .zip(vrma_node_indices.iter())
+    {
+        let Some(clip) = clips.get_mut(handle.id()) else {
+            continue;
+        };
+        register_rotate_transformation(
+            &mut commands,
+            vrma_entity,
+            *node_index,
+            root_bone,
+            registry,
+            &searcher,
+            &bones,
+        );
+        replace_bone_animation_clips(
+            &mut commands,
+            clip,
+            *node_index,
+            vrma_entity,
+            root_bone,
+            registry,
+            &searcher,
+            &bones,
+        );
+    }
 }
 
 fn replace_bone_animation_clips(
+    commands: &mut Commands,
     clip: &mut AnimationClip,
     node_index: AnimationNodeIndex,
     vrma_entity: Entity,
@@ -211,6 +308,7 @@ fn replace_bone_animation_clips(
         };
         if bone.as_str() == "hips" {
             register_hips_translation_transformation(
+                commands,
                 node_index,
                 bone_entity,
                 src_rest_gtf,
@@ -227,6 +325,27 @@ fn replace_bone_animation_clips(
     }
 }
 
+/// Removes the retarget transformations of the despawned VRMA from the bones of VRM.
+fn remove_retarget_transformations(
+    trigger: Trigger<OnRemove, VrmAnimationNodeIndices>,
+    vrmas: Query<(&VrmAnimationNodeIndices, &ChildOf)>,
+    childrens: Query<&Children>,
+    mut rotations: Query<&mut RetargetTransformations<bone_rotation::Transformation>>,
+    mut translations: Query<&mut RetargetTransformations<bone_translation::Transformation>>,
+) {
+    let Ok((node_indices, ChildOf(vrm))) = vrmas.get(trigger.target()) else {
+        return;
+    };
+    for bone in childrens.iter_descendants(*vrm) {
+        if let Ok(mut transformations) = rotations.get_mut(bone) {
+            transformations.retain(|node_index, _| !node_indices.contains(node_index));
+        }
+        if let Ok(mut transformations) = translations.get_mut(bone) {
+            transformations.retain(|node_index, _| !node_indices.contains(node_index));
+        }
+    }
+}
+
 fn animation_curve(
     original: VariableCurve,
     hips: bool,
@@ -258,7 +377,7 @@ fn animation_curve(
 fn apply_regenerate_expression_clips(
     trigger: Trigger<RequestUpdateAnimationClips>,
     mut clips: ResMut<Assets<AnimationClip>>,
-    clip_handles: Query<&VrmAnimationClipHandle>,
+    clip_handles: Query<&VrmAnimationClipHandles>,
     animation_targets: Query<&AnimationTarget>,
     expressions: Query<&VrmExpressionRegistry>,
     searcher: ChildSearcher,
@@ -271,10 +390,7 @@ fn apply_regenerate_expression_clips(
     let Some(expressions_root) = searcher.find_expressions_root(vrm_entity) else {
         return;
     };
-    let Ok(vrm_animation_clip_handle) = clip_handles.get(vrma_entity) else {
-        return;
-    };
-    let Some(clip) = clips.get_mut(vrm_animation_clip_handle.0.id()) else {
+    let Ok(vrm_animation_clip_handles) = clip_handles.get(vrma_entity) else {
         return;
     };
     let Ok(registry) = expressions.get(vrm_entity) else {
@@ -293,12 +409,115 @@ fn apply_regenerate_expression_clips(
         let Ok(target) = animation_targets.get(expression_entity) else {
             return;
         };
-        let animation_curves = clip.curves_mut();
-        if let Some(curves) = animation_curves.remove(&vrma_target.id) {
-            animation_curves.insert(target.id, curves);
+        for handle in vrm_animation_clip_handles.0.iter() {
+            let Some(clip) = clips.get_mut(handle.id()) else {
+                continue;
+            };
+            let animation_curves = clip.curves_mut();
+            if let Some(curves) = animation_curves.remove(&vrma_target.id) {
+                animation_curves.insert(target.id, curves);
+            }
         }
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::tests::test_app;
+    use crate::vrma::animation::animation_graph::{
+        RequestUpdateAnimationGraph, VrmaAnimationGraphPlugin,
+    };
+    use crate::vrma::animation::bone_translation::Transformation;
+    use crate::vrma::animation::retarget::RetargetTransformations;
+    use crate::vrma::{VrmAnimationClipHandles, VrmAnimationNodeIndices};
+    use bevy::prelude::*;
+
+    #[test]
+    fn remove_transformations_on_despawn_vrma() {
+        let mut app = test_app();
+        app.add_plugins(VrmaAnimationGraphPlugin);
+        let node_index = AnimationNodeIndex::new(1);
+        let transformation = Transformation {
+            src_rest_g: Vec3::ONE,
+            dist_rest_g: Vec3::ONE,
+        };
+        let vrm = app.world_mut().spawn_empty().id();
+        let hips = app
+            .world_mut()
+            .spawn((
+                RetargetTransformations([(node_index, transformation)].into_iter().collect()),
+                ChildOf(vrm),
+            ))
+            .id();
+        let vrma = app
+            .world_mut()
+            .spawn((VrmAnimationNodeIndices(vec![node_index]), ChildOf(vrm)))
+            .id();
+
+        app.world_mut().entity_mut(vrma).despawn();
+        let transformations = app
+            .world()
+            .get::<RetargetTransformations<Transformation>>(hips)
+            .unwrap();
+        assert!(transformations.is_empty());
+    }
+
+    #[test]
+    fn move_transformations_on_rebuild_graph() {
+        let mut app = test_app();
+        app.init_asset::<AnimationGraph>()
+            .init_asset::<AnimationClip>()
+            .add_plugins(VrmaAnimationGraphPlugin);
+        let transformation_a = Transformation {
+            src_rest_g: Vec3::ONE,
+            dist_rest_g: Vec3::ONE,
+        };
+        let transformation_b = Transformation {
+            src_rest_g: Vec3::X,
+            dist_rest_g: Vec3::Y,
+        };
+        let vrm = app.world_mut().spawn_empty().id();
+        let spawn_vrma = |app: &mut App| {
+            let vrma = app
+                .world_mut()
+                .spawn((
+                    VrmAnimationClipHandles(vec![Handle::default()]),
+                    ChildOf(vrm),
+                ))
+                .id();
+            app.world_mut()
+                .trigger(RequestUpdateAnimationGraph { vrm, vrma });
+            app.world_mut().flush();
+            vrma
+        };
+        let node_of = |app: &App, vrma: Entity| {
+            app.world().get::<VrmAnimationNodeIndices>(vrma).unwrap().0[0]
+        };
+        let vrma_a = spawn_vrma(&mut app);
+        let vrma_b = spawn_vrma(&mut app);
+        let node_a = node_of(&app, vrma_a);
+        let node_b = node_of(&app, vrma_b);
+        let hips = app
+            .world_mut()
+            .spawn((
+                RetargetTransformations(
+                    [(node_a, transformation_a), (node_b, transformation_b)]
+                        .into_iter()
+                        .collect(),
+                ),
+                ChildOf(vrm),
+            ))
+            .id();
+
+        app.world_mut().entity_mut(vrma_a).despawn();
+        spawn_vrma(&mut app);
+        let node_b = node_of(&app, vrma_b);
+        assert_eq!(node_b, node_a);
+        let transformations = app
+            .world()
+            .get::<RetargetTransformations<Transformation>>(hips)
+            .unwrap();
+        assert_eq!(transformations.len(), 1);
+        assert_eq!(transformations.get(&node_b).unwrap().dist_rest_g, Vec3::Y);
+    }
+}
@@ -0,0 +1,513 @@
+//! Blends the animations of multiple VRMAs by parameters, such as idle, walk and run by speed.
+//!
+//! The animations of the samples are played in sync with the same normalized time,
+//! so the footsteps of the clips with different durations match each other.
+//! Each sample is played by the player at the speed scaled by the ratio of its duration to the blended duration,
+//! so the markers and [`VrmaLooped`](crate::prelude::VrmaLooped) are fired as usual.
+
+use crate::prelude::ChildSearcher;
+use crate::vrma::animation::vrm_animation_players;
+use crate::vrma::{VrmAnimationNodeIndices, VrmaClipSelector, VrmaClips};
+use bevy::animation::AnimationPlayer;
+use bevy::app::{Animation, App, Plugin};
+use bevy::prelude::*;
+
+/// The blend space that plays the animations of the VRMAs under the VRM weighted by [`VrmaBlendSpace::parameter`].
+///
+/// Spawn this component as a child of the VRM entity like VRMA to play, and despawn it to stop.
+/// The animations are blended with the others played by [`PlayVrma`](crate::prelude::PlayVrma) as the base animation,
+/// so the layers played by [`PlayVrmaLayer`](crate::prelude::PlayVrmaLayer) are applied over it.
+///
+/// The weights are calculated by gradient band interpolation.
+/// For 1D blend spaces, put all samples on the x-axis and the animations are linearly interpolated between the neighboring samples.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn start_locomotion(
+///     mut commands: Commands,
+///     vrm: Single<Entity, With<Vrm>>,
+///     vrmas: Query<(Entity, &Name), With<Vrma>>,
+/// ) {
+///     let samples = vrmas
+///         .iter()
+///         .filter_map(|(vrma, name)| {
+///             let speed = match name.as_str() {
+///                 "idle" => 0.0,
+///                 "walk" => 1.5,
+///                 "run" => 4.0,
+///                 _ => return None,
+///             };
+///             Some(VrmaBlendSample::new(vrma, Vec2::new(speed, 0.0)))
+///         })
+///         .collect();
+///     commands.spawn((
+///         VrmaBlendSpace {
+///             samples,
+///             ..default()
+///         },
+///         ChildOf(*vrm),
+///     ));
+/// }
+/// ```
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaBlendSpace {
+    pub samples: Vec<VrmaBlendSample>,
+    /// The current coordinates in the blend space.
+    /// Only `x` is used for 1D blend spaces.
+    pub parameter: Vec2,
+    /// The playback speed of the blend space.
+    /// Default is `1.0`.
+    pub speed: f32,
+    /// The weight of the blend space relative to the animation played by [`PlayVrma`](crate::prelude::PlayVrma) and the other blend spaces.
+    /// Default is `1.0`.
+    pub weight: f32,
+}
+
+impl Default for VrmaBlendSpace {
+    fn default() -> Self {
+        Self {
+            samples: Vec::new(),
+            parameter: Vec2::ZERO,
+            speed: 1.0,
+            weight: 1.0,
+        }
+    }
+}
+
+/// The animation placed at [`VrmaBlendSample::position`] in [`VrmaBlendSpace`].
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaBlendSample {
+    /// The entity of VRMA, which must be a child of the VRM.
+    pub vrma: Entity,
+    /// The animation to play among [`VrmaClips`].
+    pub clip: VrmaClipSelector,
+    /// The coordinates of this sample in the blend space.
+    pub position: Vec2,
+}
+
+impl VrmaBlendSample {
+    /// Creates the sample of the first animation of VRMA.
+    pub fn new(
+        vrma: Entity,
+        position: Vec2,
+    ) -> Self {
+        Self {
+            vrma,
+            clip: VrmaClipSelector::default(),
+            position,
+        }
+    }
+}
+
+pub(super) struct VrmaBlendSpacePlugin;
+
+impl Plugin for VrmaBlendSpacePlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<VrmaBlendSpace>()
+            .register_type::<VrmaBlendSample>()
+            .add_observer(stop_blend_space)
+            .add_systems(PostUpdate, update_blend_spaces.before(Animation));
+    }
+}
+
+/// The normalized time shared by the animations of [`VrmaBlendSpace`].
+///
+/// This is increased by `1.0` each loop.
+#[derive(Component, Debug, Default)]
+pub(crate) struct VrmaBlendSpaceTime(pub(crate) f32);
+
+/// The animation nodes played by [`VrmaBlendSpace`], which are stopped when their samples are removed.
+#[derive(Component, Debug, Default)]
+struct VrmaBlendSpaceNodes(Vec<AnimationNodeIndex>);
+
+struct ResolvedSample {
+    node_index: AnimationNodeIndex,
+    duration: f32,
+    weight: f32,
+}
+
+fn update_blend_spaces(
+    mut commands: Commands,
+    mut players: Query<&mut AnimationPlayer>,
+    mut blend_spaces: Query<(
+        Entity,
+        &ChildOf,
+        &VrmaBlendSpace,
+        Option<&mut VrmaBlendSpaceTime>,
+        Option<&mut VrmaBlendSpaceNodes>,
+    )>,
+    vrmas: Query<(&VrmAnimationNodeIndices, &VrmaClips)>,
+    time: Res<Time>,
+    searcher: ChildSearcher,
+    childrens: Query<&Children>,
+) {
+    for (entity, ChildOf(vrm), blend_space, previous_time, played_nodes) in blend_spaces.iter_mut()
+    {
+        let positions = blend_space
+            .samples
+            .iter()
+            .map(|sample| sample.position)
+            .collect::<Vec<_>>();
+        let samples = blend_space
+            .samples
+            .iter()
+            .zip(sample_weights(&positions, blend_space.parameter))
+            .filter_map(|(sample, weight)| {
+                let (node_indices, clips) = vrmas.get(sample.vrma).ok()?;
+                let index = clips.index_of(&sample.clip)?;
+                Some(ResolvedSample {
+                    node_index: *node_indices.get(index)?,
+                    duration: clips[index].duration.as_secs_f32(),
+                    weight,
+                })
+            })
+            .collect::<Vec<_>>();
+        // The duration of the blended animation, which is used to advance the normalized time.
+        // This does not depend on `VrmaBlendSpace::weight`, so it is kept while the blend space is faded out to zero.
+        let total_weight = samples.iter().map(|sample| sample.weight).sum::<f32>();
+        let duration = if 0. < total_weight {
+            samples
+                .iter()
+                .map(|sample| sample.duration * sample.weight)
+                .sum::<f32>()
+                / total_weight
+        } else {
+            0.
+        };
+        let node_indices = samples
+            .iter()
+            .map(|sample| sample.node_index)
+            .collect::<Vec<_>>();
+        let removed_nodes = played_nodes
+            .as_ref()
+            .map(|played| {
+                played
+                    .0
+                    .iter()
+                    .filter(|node_index| !node_indices.contains(node_index))
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let previous = previous_time.as_ref().map(|t| t.0).unwrap_or_default();
+        let normalized_time = if 0. < duration {
+            previous + time.delta_secs() * blend_space.speed / duration
+        } else {
+            previous
+        };
+        for player_entity in vrm_animation_players(*vrm, &searcher, &childrens) {
+            let Ok(mut player) = players.get_mut(player_entity) else {
+                continue;
+            };
+            for node_index in removed_nodes.iter() {
+                player.stop(*node_index);
+            }
+            for sample in samples.iter() {
+                // The samples started later join at the current normalized time without firing the skipped markers.
+                let animation = match player.animation_mut(sample.node_index) {
+                    Some(animation) => animation,
+                    None => player
+                        .start(sample.node_index)
+                        .repeat()
+                        .set_seek_time(previous.rem_euclid(1.) * sample.duration),
+                };
+                // All samples advance the same normalized time by playing at the speed relative to the blended duration.
+                let speed = if 0. < duration {
+                    sample.duration / duration * blend_space.speed
+                } else {
+                    0.
+                };
+                animation
+                    .set_weight(sample.weight * blend_space.weight)
+                    .set_speed(speed);
+            }
+        }
+        match previous_time {
+            Some(mut previous_time) => previous_time.0 = normalized_time,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(VrmaBlendSpaceTime(normalized_time));
+            }
+        }
+        match played_nodes {
+            Some(mut played_nodes) => played_nodes.0 = node_indices,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(VrmaBlendSpaceNodes(node_indices));
+            }
+        }
+    }
+}
+
+/// Stops the animations of the blend space when it is despawned or replaced.
+fn stop_blend_space(
+    trigger: Trigger<OnReplace, VrmaBlendSpace>,
+    mut players: Query<&mut AnimationPlayer>,
+    blend_spaces: Query<(&ChildOf, &VrmaBlendSpaceNodes)>,
+    searcher: ChildSearcher,
+    childrens: Query<&Children>,
+) {
+    let Ok((ChildOf(vrm), played_nodes)) = blend_spaces.get(trigger.target()) else {
+        return;
+    };
+    for entity in vrm_animation_players(*vrm, &searcher, &childrens) {
+        let Ok(mut player) = players.get_mut(entity) else {
+            continue;
+        };
+        for node_index in played_nodes.0.iter() {
+            player.stop(*node_index);
+        }
+    }
+}
+
+/// Calculates the weight of each sample by gradient band interpolation.
+///
+/// The weights are normalized so that the total is `1.0`.
+fn sample_weights(
+    positions: &[Vec2],
+    parameter: Vec2,
+) -> Vec<f32> {
+    let weights = positions
+        .iter()
+        .enumerate()
+        .map(|(i, pi)| {
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(j, pj)| i != *j && pi != *pj)
+                .map(|(_, pj)| {
+                    let band = *pj - *pi;
+                    (1. - (parameter - *pi).dot(band) / band.length_squared()).clamp(0., 1.)
+                })
+                .fold(1., f32::min)
+        })
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f32>();
+    if total <= 0. {
+        return weights;
+    }
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::VrmAnimationClipHandles;
+    use crate::vrma::animation::blend_space::{VrmaBlendSpacePlugin, sample_weights};
+    use crate::vrma::animation::markers::VrmaMarkersPlugin;
+    use bevy::animation::ActiveAnimation;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[test]
+    fn interpolate_between_neighboring_samples() {
+        let positions = [Vec2::ZERO, Vec2::new(1.5, 0.), Vec2::new(4., 0.)];
+        let weights = sample_weights(&positions, Vec2::new(2.75, 0.));
+        assert_eq!(weights, [0., 0.5, 0.5]);
+
+        let positions = [Vec2::ZERO, Vec2::X, Vec2::NEG_X, Vec2::Y];
+        let weights = sample_weights(&positions, Vec2::Y);
+        assert_eq!(weights, [0., 0., 0., 1.]);
+    }
+
+    struct Setup {
+        app: App,
+        vrm: Entity,
+        walk: Entity,
+        run: Entity,
+        root_bone: Entity,
+        nodes: Vec<AnimationNodeIndex>,
+    }
+
+    /// Spawns a VRM with the walk VRMA of 1 second and the run VRMA of 3 seconds.
+    fn setup() -> Setup {
+        let mut app = test_app();
+        app.add_plugins((AnimationPlugin, VrmaBlendSpacePlugin, VrmaMarkersPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )));
+
+        let mut clips = app.world_mut().resource_mut::<Assets<AnimationClip>>();
+        let mut clip = |seconds: u64| {
+            let mut clip = AnimationClip::default();
+            clip.set_duration(seconds as f32);
+            clips.add(clip)
+        };
+        let clips = [clip(1), clip(3)];
+        let (graph, nodes) = AnimationGraph::from_clips(clips.clone());
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let TestVrm {
+            vrm,
+            root_bone,
+            vrmas,
+        } = spawn_vrm_with_vrmas(&mut app, graph, &nodes);
+        for (vrma, clip) in vrmas.iter().zip(clips) {
+            app.world_mut()
+                .entity_mut(*vrma)
+                .insert(VrmAnimationClipHandles(vec![clip]));
+        }
+        let (walk, run) = (vrmas[0], vrmas[1]);
+        app.world_mut()
+            .entity_mut(run)
+            .insert(VrmaClips(vec![VrmaClip {
+                name: None,
+                duration: Duration::from_secs(3),
+                markers: Vec::new(),
+            }]));
+        Setup {
+            app,
+            vrm,
+            walk,
+            run,
+            root_bone,
+            nodes,
+        }
+    }
+
+    impl Setup {
+        fn spawn_blend_space(&mut self) -> Entity {
+            self.app
+                .world_mut()
+                .spawn((
+                    VrmaBlendSpace {
+                        samples: vec![
+                            VrmaBlendSample::new(self.walk, Vec2::ZERO),
+                            VrmaBlendSample::new(self.run, Vec2::X),
+                        ],
+                        parameter: Vec2::new(0.5, 0.),
+                        ..default()
+                    },
+                    ChildOf(self.vrm),
+                ))
+                .id()
+        }
+
+        fn animation(
+            &self,
+            index: usize,
+        ) -> Option<&ActiveAnimation> {
+            self.app
+                .world()
+                .get::<AnimationPlayer>(self.root_bone)
+                .unwrap()
+                .animation(self.nodes[index])
+        }
+    }
+
+    #[test]
+    fn synchronize_normalized_time() {
+        let mut setup = setup();
+        let blend_space = setup.spawn_blend_space();
+        for _ in 0..5 {
+            setup.app.update();
+        }
+
+        // The blended duration is 2 seconds, so the walk of 1 second plays at half speed and the run at 1.5 times.
+        let walk_animation = setup.animation(0).unwrap();
+        let run_animation = setup.animation(1).unwrap();
+        assert_eq!(walk_animation.speed(), 0.5);
+        assert_eq!(run_animation.speed(), 1.5);
+        let normalized_time = walk_animation.seek_time();
+        assert!(0. < normalized_time);
+        assert!(
+            (run_animation.seek_time() - normalized_time * 3.).abs() < 1e-5,
+            "{normalized_time}"
+        );
+        assert_eq!(walk_animation.weight(), 0.5);
+        assert_eq!(run_animation.weight(), 0.5);
+
+        setup.app.world_mut().entity_mut(blend_space).despawn();
+        setup.app.update();
+        assert!(setup.animation(0).is_none());
+    }
+
+    #[test]
+    fn stop_removed_sample() {
+        let mut setup = setup();
+        let blend_space = setup.spawn_blend_space();
+        setup.app.update();
+        setup.app.update();
+        assert!(setup.animation(1).is_some());
+
+        setup
+            .app
+            .world_mut()
+            .get_mut::<VrmaBlendSpace>(blend_space)
+            .unwrap()
+            .samples
+            .pop();
+        setup.app.update();
+        assert!(setup.animation(1).is_none());
+        assert_eq!(setup.animation(0).map(|a| a.weight()), Some(1.));
+    }
+
+    #[test]
+    fn keep_speed_with_zero_weight() {
+        let mut setup = setup();
+        let blend_space = setup.spawn_blend_space();
+        setup.app.update();
+        setup
+            .app
+            .world_mut()
+            .get_mut::<VrmaBlendSpace>(blend_space)
+            .unwrap()
+            .weight = 0.;
+        setup.app.update();
+
+        let walk_animation = setup.animation(0).unwrap();
+        assert_eq!(walk_animation.weight(), 0.);
+        assert_eq!(walk_animation.speed(), 0.5);
+        assert!(walk_animation.seek_time().is_finite());
+    }
+
+    #[derive(Resource, Default)]
+    struct Reached(Vec<String>);
+
+    #[test]
+    fn fire_markers_in_blend_space() {
+        let mut setup = setup();
+        setup.app.init_resource::<Reached>().add_observer(
+            |trigger: Trigger<VrmaMarkerReached>, mut reached: ResMut<Reached>| {
+                reached.0.push(trigger.marker.name.clone());
+            },
+        );
+        let walk = setup.walk;
+        setup
+            .app
+            .world_mut()
+            .commands()
+            .entity(walk)
+            .trigger(AddVrmaMarker {
+                clip: VrmaClipSelector::default(),
+                marker: VrmaMarker {
+                    name: "footstep".to_string(),
+                    time: 0.5,
+                },
+            });
+        setup.spawn_blend_space();
+        // The normalized time reaches 0.75 in 1.5 seconds, passing the marker of the walk once.
+        for _ in 0..15 {
+            setup.app.update();
+        }
+
+        assert_eq!(setup.app.world().resource::<Reached>().0, ["footstep"]);
+    }
+}
@@ -1,15 +1,15 @@
 use crate::prelude::*;
 use crate::vrm::humanoid_bone::HumanoidBoneRegistry;
-use bevy::animation::{
-    AnimationEntityMut, AnimationEvaluationError, AnimationTarget, animated_field,
+use crate::vrma::animation::retarget::{
+    RetargetCurveEvaluator, RetargetTransformation, register_retarget_transformation,
 };
-use bevy::platform::collections::HashMap;
+use bevy::animation::{AnimationEvaluationError, AnimationTarget, animated_field};
 use bevy::prelude::*;
 use std::any::TypeId;
 use std::fmt::{Debug, Formatter};
-use std::sync::Mutex;
 
 pub(crate) fn register_rotate_transformation(
+    commands: &mut Commands,
     vrma: Entity,
     node_index: AnimationNodeIndex,
     root_bone: Entity,
@@ -21,56 +21,26 @@ pub(crate) fn register_rotate_transformation(
         &AnimationTarget,
     )>,
 ) {
-    let transformations =
-        BoneRotateTransformations::new(vrma, node_index, root_bone, registry, searcher, bones);
-    BONE_ROTATION_TRANSFORMATIONS
-        .lock()
-        .expect("Failed to lock BONE_ROTATION_TRANSFORMATIONS")
-        .extend(transformations.0);
-}
-
-static BONE_ROTATION_TRANSFORMATIONS: Mutex<HashMap<(Entity, AnimationNodeIndex), Transformation>> =
-    Mutex::new(HashMap::new());
-
-#[derive(Clone, Debug, Deref, DerefMut)]
-struct BoneRotateTransformations(pub HashMap<(Entity, AnimationNodeIndex), Transformation>);
-
-impl BoneRotateTransformations {
-    pub fn new(
-        vrma: Entity,
-        node_index: AnimationNodeIndex,
-        root_bone: Entity,
-        registry: &HumanoidBoneRegistry,
-        searcher: &ChildSearcher,
-        bones: &Query<(
-            &BoneRestTransform,
-            &BoneRestGlobalTransform,
-            &AnimationTarget,
-        )>,
-    ) -> Self {
-        let mut transformations = HashMap::new();
-        for (bone, name) in registry.iter() {
-            let Some(vrma_bone_entity) = searcher.find_from_name(vrma, name) else {
-                continue;
-            };
-            let Some(rig_bone_entity) = searcher.find_by_bone_name(root_bone, bone) else {
-                continue;
-            };
-            let Some((rest, rest_g, _)) = bones.get(rig_bone_entity).ok() else {
-                continue;
-            };
-            let Some((vrma_rest, vrma_rest_g, _)) = bones.get(vrma_bone_entity).ok() else {
-                continue;
-            };
-            let transformation = Transformation {
-                src_rest: vrma_rest.0.rotation,
-                src_rest_g: vrma_rest_g.0.rotation(),
-                dist_rest: rest.0.rotation,
-                dist_rest_g: rest_g.0.rotation(),
-            };
-            transformations.insert((rig_bone_entity, node_index), transformation);
-        }
-        Self(transformations)
+    for (bone, name) in registry.iter() {
+        let Some(vrma_bone_entity) = searcher.find_from_name(vrma, name) else {
+            continue;
+        };
+        let Some(rig_bone_entity) = searcher.find_by_bone_name(root_bone, bone) else {
+            continue;
+        };
+        let Some((rest, rest_g, _)) = bones.get(rig_bone_entity).ok() else {
+            continue;
+        };
+        let Some((vrma_rest, vrma_rest_g, _)) = bones.get(vrma_bone_entity).ok() else {
+            continue;
+        };
+        let transformation = Transformation {
+            src_rest: vrma_rest.0.rotation,
+            src_rest_g: vrma_rest_g.0.rotation(),
+            dist_rest: rest.0.rotation,
+            dist_rest_g: rest_g.0.rotation(),
+        };
+        register_retarget_transformation(commands, rig_bone_entity, node_index, transformation);
     }
 }
 
@@ -82,8 +52,10 @@ pub(crate) struct Transformation {
     dist_rest_g: Quat,
 }
 
-impl Transformation {
-    pub fn transform(
+impl RetargetTransformation for Transformation {
+    type Value = Quat;
+
+    fn transform(
         &self,
         src_pose: Quat,
     ) -> Quat {
@@ -92,6 +64,19 @@ impl Transformation {
             self.src_rest_g * self.src_rest.inverse() * src_pose * self.src_rest_g.inverse();
         self.dist_rest * self.dist_rest_g.inverse() * normalized_local_rotation * self.dist_rest_g
     }
+
+    fn delta(
+        &self,
+        src_pose: Quat,
+    ) -> Quat {
+        // The additive rotation is multiplied from the left of the pose.
+        self.transform(src_pose) * self.dist_rest.inverse()
+    }
+
+    #[inline]
+    fn rest(&self) -> Quat {
+        self.dist_rest
+    }
 }
 
 pub struct BoneRotationAnimationCurve {
@@ -119,16 +104,14 @@ impl AnimationCurve for BoneRotationAnimationCurve {
     }
 
     fn evaluator_id(&self) -> EvaluatorId {
-        EvaluatorId::Type(TypeId::of::<Self>())
+        EvaluatorId::Type(TypeId::of::<RetargetCurveEvaluator<Transformation>>())
     }
 
     fn create_evaluator(&self) -> Box<dyn AnimationCurveEvaluator> {
-        Box::new(Evaluator {
-            base: self.base.create_evaluator(),
-            property: Box::new(animated_field!(Transform::rotation)),
-            nodes: Vec::default(),
-            transformations: HashMap::default(),
-        })
+        Box::new(RetargetCurveEvaluator::<Transformation>::new(
+            self.base.create_evaluator(),
+            Box::new(animated_field!(Transform::rotation)),
+        ))
     }
 
     fn apply(
@@ -138,78 +121,193 @@ impl AnimationCurve for BoneRotationAnimationCurve {
         weight: f32,
         graph_node: AnimationNodeIndex,
     ) -> Result<(), AnimationEvaluationError> {
-        let Some(curve_evaluator) = curve_evaluator.downcast_mut::<Evaluator>() else {
-            let ty = TypeId::of::<Evaluator>();
+        let Some(curve_evaluator) =
+            curve_evaluator.downcast_mut::<RetargetCurveEvaluator<Transformation>>()
+        else {
+            let ty = TypeId::of::<RetargetCurveEvaluator<Transformation>>();
             return Err(AnimationEvaluationError::InconsistentEvaluatorImplementation(ty));
         };
-        curve_evaluator.nodes.push(graph_node);
-        self.base
-            .apply(&mut *curve_evaluator.base, t, weight, graph_node)?;
-        //FIXME: Currently, blending multiple VRMAs with different initial poses results in incorrect interpolation.
-        // To fix this, we need to implement the following at this timing, but we cannot do it due to access scope issues.
-        // let curve_evaluator = curve_evaluator
-        //     .downcast_mut::<AnimatableCurveEvaluator<Quat>>()
-        //     .unwrap();
-        // let e = curve_evaluator
-        //     .evaluator
-        //     .stack
-        //     .pop()
-        //     .unwrap();
-        // curve_evaluator.evaluator.stack.push(BasicAnimationCurveEvaluatorStackElement{
-        //     value: self.transformations.0.get(graph_node).unwrap().transform(e.value),
-        //     weight,
-        //     graph_node,
-        // });
-        Ok(())
+        curve_evaluator.sample(&*self.base, t, weight, graph_node)
     }
 }
 
-struct Evaluator {
-    base: Box<dyn AnimationCurveEvaluator>,
-    property: Box<dyn AnimatableProperty<Property = Quat>>,
-    nodes: Vec<AnimationNodeIndex>,
-    transformations: HashMap<(Entity, AnimationNodeIndex), Transformation>,
-}
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_app;
+    use crate::vrma::animation::animation_graph::vrm_animation_graph;
+    use crate::vrma::animation::bone_rotation::{BoneRotationAnimationCurve, Transformation};
+    use crate::vrma::animation::retarget::RetargetTransformations;
+    use bevy::animation::{AnimationTarget, AnimationTargetId, animated_field};
+    use bevy::prelude::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
 
-impl AnimationCurveEvaluator for Evaluator {
-    fn blend(
-        &mut self,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.blend(graph_node)
+    fn clip(
+        target_id: AnimationTargetId,
+        rotation: Quat,
+    ) -> AnimationClip {
+        let mut clip = AnimationClip::default();
+        clip.add_variable_curve_to_target(
+            target_id,
+            VariableCurve(Box::new(BoneRotationAnimationCurve {
+                base: Box::new(AnimatableCurve::new(
+                    animated_field!(Transform::rotation),
+                    ConstantCurve::new(Interval::EVERYWHERE, rotation),
+                )),
+            })),
+        );
+        clip
     }
 
-    fn add(
-        &mut self,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.add(graph_node)
+    fn transformation(src_rest: Quat) -> Transformation {
+        Transformation {
+            src_rest,
+            src_rest_g: src_rest,
+            dist_rest: Quat::IDENTITY,
+            dist_rest_g: Quat::IDENTITY,
+        }
     }
 
-    fn push_blend_register(
-        &mut self,
+    #[test]
+    fn crossfade_vrmas_with_different_rest_poses() {
+        let mut app = test_app();
+        app.add_plugins(AnimationPlugin);
+        let target_id = AnimationTargetId::from_name(&Name::new("bone"));
+        let clip = |rotation: Quat| clip(target_id, rotation);
+        // Both VRMAs stay in their own rest pose, so the retargeted pose must be the rest pose of VRM
+        // at any point of the transition.
+        let bent_rest = Quat::from_rotation_x(FRAC_PI_2);
+        let mut clips = app.world_mut().resource_mut::<Assets<AnimationClip>>();
+        let straight_clip = clips.add(clip(Quat::IDENTITY));
+        let bent_clip = clips.add(clip(bent_rest));
+        let (graph, nodes) = AnimationGraph::from_clips([straight_clip, bent_clip]);
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let mut player = AnimationPlayer::default();
+        player.play(nodes[0]).set_weight(0.5).repeat();
+        player.play(nodes[1]).set_weight(0.5).repeat();
+        let player = app
+            .world_mut()
+            .spawn((player, AnimationGraphHandle(graph)))
+            .id();
+        let bone = app
+            .world_mut()
+            .spawn((
+                Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+                AnimationTarget {
+                    id: target_id,
+                    player,
+                },
+                RetargetTransformations(
+                    [
+                        (nodes[0], transformation(Quat::IDENTITY)),
+                        (nodes[1], transformation(bent_rest)),
+                    ]
+                    .into_iter()
+                    .collect(),
+                ),
+            ))
+            .id();
+        // The animation graph is applied in the next frame after it is added.
+        app.update();
+        app.update();
+
+        let rotation = app.world().get::<Transform>(bone).unwrap().rotation;
+        assert!(rotation.angle_between(Quat::IDENTITY) < 1e-4, "{rotation}");
+    }
+
+    /// Plays `additive` as an additive layer with `weight` over `base`, and returns the rotation of the bone.
+    fn add_delta(
+        base: Option<Quat>,
+        additive: Quat,
         weight: f32,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.push_blend_register(weight, graph_node)
-    }
-
-    fn commit(
-        &mut self,
-        mut entity: AnimationEntityMut,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        let bone_entity = entity.id();
-        self.base.commit(entity.reborrow())?;
-        let node_index = self.nodes.pop().unwrap();
-        let transformation = self
-            .transformations
-            .entry((bone_entity, node_index))
-            .or_insert_with(|| {
-                let t = BONE_ROTATION_TRANSFORMATIONS.lock().unwrap();
-                t.get(&(bone_entity, node_index)).cloned().unwrap()
-            });
-        let rotate = self.property.get_mut(&mut entity)?;
-        *rotate = transformation.transform(*rotate);
-        Ok(())
+        dist_rest: Quat,
+    ) -> Quat {
+        let mut app = test_app();
+        app.add_plugins(AnimationPlugin);
+        let target_id = AnimationTargetId::from_name(&Name::new("bone"));
+        let bent_rest = Quat::from_rotation_x(FRAC_PI_2);
+        let mut clips = app.world_mut().resource_mut::<Assets<AnimationClip>>();
+        let base_clip = clips.add(clip(target_id, base.unwrap_or_default()));
+        let additive_clip = clips.add(clip(target_id, additive * bent_rest));
+        let (mut graph, nodes) = vrm_animation_graph([base_clip, additive_clip]);
+        let base_node = graph.graph.neighbors(graph.root).min().unwrap();
+        graph.remove_edge(base_node, nodes[1]);
+        graph.add_edge(graph.root, nodes[1]);
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let mut player = AnimationPlayer::default();
+        if base.is_some() {
+            player.play(nodes[0]).repeat();
+        }
+        // `VrmaLayer::weight` is applied as the weight of the additive animation.
+        player.play(nodes[1]).set_weight(weight).repeat();
+        let player = app
+            .world_mut()
+            .spawn((player, AnimationGraphHandle(graph)))
+            .id();
+        let transformation = |src_rest: Quat| Transformation {
+            src_rest,
+            src_rest_g: src_rest,
+            dist_rest,
+            dist_rest_g: dist_rest,
+        };
+        let bone = app
+            .world_mut()
+            .spawn((
+                Transform::from_rotation(dist_rest),
+                AnimationTarget {
+                    id: target_id,
+                    player,
+                },
+                RetargetTransformations(
+                    [
+                        (nodes[0], transformation(Quat::IDENTITY)),
+                        (nodes[1], transformation(bent_rest)),
+                    ]
+                    .into_iter()
+                    .collect(),
+                ),
+            ))
+            .id();
+        // The animation graph is applied in the next frame after it is added.
+        app.update();
+        app.update();
+
+        app.world().get::<Transform>(bone).unwrap().rotation
+    }
+
+    #[test]
+    fn add_delta_from_rest_pose_of_vrma() {
+        // The additive VRMA stays in its own rest pose, so it must not change the base pose.
+        let base_pose = Quat::from_rotation_y(FRAC_PI_2);
+        let rotation = add_delta(Some(base_pose), Quat::IDENTITY, 1., Quat::IDENTITY);
+        assert!(rotation.abs_diff_eq(base_pose, 1e-4), "{rotation}");
+    }
+
+    #[test]
+    fn add_weighted_delta_to_base_pose() {
+        let base_pose = Quat::from_rotation_y(FRAC_PI_2);
+        let rotation = add_delta(
+            Some(base_pose),
+            Quat::from_rotation_z(FRAC_PI_2),
+            0.5,
+            Quat::IDENTITY,
+        );
+        let expected = Quat::from_rotation_z(FRAC_PI_4) * base_pose;
+        assert!(rotation.abs_diff_eq(expected, 1e-4), "{rotation}");
+    }
+
+    #[test]
+    fn add_delta_to_rest_pose_of_vrm_without_base_animation() {
+        let dist_rest = Quat::from_rotation_y(FRAC_PI_2);
+        let rotation = add_delta(None, Quat::from_rotation_z(FRAC_PI_2), 0.5, dist_rest);
+        let expected = Quat::from_rotation_z(FRAC_PI_4) * dist_rest;
+        assert!(rotation.abs_diff_eq(expected, 1e-4), "{rotation}");
     }
 }
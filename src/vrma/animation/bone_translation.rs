@@ -1,30 +1,26 @@
 use crate::prelude::BoneRestGlobalTransform;
-use bevy::animation::{AnimationEntityMut, AnimationEvaluationError, animated_field};
-use bevy::platform::collections::HashMap;
+use crate::vrma::animation::retarget::{
+    RetargetCurveEvaluator, RetargetTransformation, register_retarget_transformation,
+};
+use bevy::animation::{AnimationEvaluationError, animated_field};
 use bevy::prelude::*;
 use std::any::TypeId;
 use std::fmt::{Debug, Formatter};
-use std::sync::Mutex;
 
-pub fn register_hips_translation_transformation(
+pub(crate) fn register_hips_translation_transformation(
+    commands: &mut Commands,
     node_index: AnimationNodeIndex,
     hips: Entity,
     src_rest_g: &BoneRestGlobalTransform,
     dist_reg_g: &BoneRestGlobalTransform,
 ) {
-    let transformations = Transformation {
+    let transformation = Transformation {
         src_rest_g: src_rest_g.translation(),
         dist_rest_g: dist_reg_g.translation(),
     };
-    HIPS_TRANSFORMATIONS
-        .lock()
-        .expect("Failed to lock HIPS_TRANSFORMATIONS")
-        .insert((hips, node_index), transformations);
+    register_retarget_transformation(commands, hips, node_index, transformation);
 }
 
-static HIPS_TRANSFORMATIONS: Mutex<HashMap<(Entity, AnimationNodeIndex), Transformation>> =
-    Mutex::new(HashMap::new());
-
 pub(crate) struct HipsTranslationAnimationCurve {
     pub base: Box<dyn AnimationCurve>,
 }
@@ -53,16 +49,14 @@ impl AnimationCurve for HipsTranslationAnimationCurve {
 
     #[inline]
     fn evaluator_id(&self) -> EvaluatorId {
-        EvaluatorId::Type(TypeId::of::<RetargetEvaluator>())
+        EvaluatorId::Type(TypeId::of::<RetargetCurveEvaluator<Transformation>>())
     }
 
     fn create_evaluator(&self) -> Box<dyn AnimationCurveEvaluator> {
-        Box::new(RetargetEvaluator {
-            base: self.base.create_evaluator(),
-            property: Box::new(animated_field!(Transform::translation)),
-            nodes: Vec::new(),
-            transformations: HashMap::new(),
-        })
+        Box::new(RetargetCurveEvaluator::<Transformation>::new(
+            self.base.create_evaluator(),
+            Box::new(animated_field!(Transform::translation)),
+        ))
     }
 
     fn apply(
@@ -72,88 +66,44 @@ impl AnimationCurve for HipsTranslationAnimationCurve {
         weight: f32,
         graph_node: AnimationNodeIndex,
     ) -> Result<(), AnimationEvaluationError> {
-        let Some(curve_evaluator) = curve_evaluator.downcast_mut::<RetargetEvaluator>() else {
-            let ty = TypeId::of::<RetargetEvaluator>();
+        let Some(curve_evaluator) =
+            curve_evaluator.downcast_mut::<RetargetCurveEvaluator<Transformation>>()
+        else {
+            let ty = TypeId::of::<RetargetCurveEvaluator<Transformation>>();
             return Err(AnimationEvaluationError::InconsistentEvaluatorImplementation(ty));
         };
-        curve_evaluator.nodes.push(graph_node);
-        self.base
-            .apply(&mut *curve_evaluator.base, t, weight, graph_node)?;
-        Ok(())
+        curve_evaluator.sample(&*self.base, t, weight, graph_node)
     }
 }
 
 #[derive(Debug, Copy, Clone, Reflect)]
-struct Transformation {
-    src_rest_g: Vec3,
-    dist_rest_g: Vec3,
+pub(crate) struct Transformation {
+    pub(crate) src_rest_g: Vec3,
+    pub(crate) dist_rest_g: Vec3,
 }
 
-impl Transformation {
-    pub fn transform(
+impl RetargetTransformation for Transformation {
+    type Value = Vec3;
+
+    #[inline]
+    fn transform(
         &self,
         src_pose: Vec3,
     ) -> Vec3 {
         calc_hips_position(self.src_rest_g, src_pose, self.dist_rest_g)
     }
-}
-
-struct RetargetEvaluator {
-    base: Box<dyn AnimationCurveEvaluator>,
-    property: Box<dyn AnimatableProperty<Property = Vec3>>,
-    nodes: Vec<AnimationNodeIndex>,
-    transformations: HashMap<(Entity, AnimationNodeIndex), Transformation>,
-}
-
-impl AnimationCurveEvaluator for RetargetEvaluator {
-    #[inline]
-    fn blend(
-        &mut self,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.blend(graph_node)
-    }
-
-    #[inline]
-    fn add(
-        &mut self,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.add(graph_node)
-    }
 
     #[inline]
-    fn push_blend_register(
-        &mut self,
-        weight: f32,
-        graph_node: AnimationNodeIndex,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        self.base.push_blend_register(weight, graph_node)
+    fn delta(
+        &self,
+        src_pose: Vec3,
+    ) -> Vec3 {
+        calc_delta(src_pose, self.src_rest_g) * calc_scaling(self.dist_rest_g, self.src_rest_g)
     }
 
     #[inline]
-    fn commit(
-        &mut self,
-        mut entity: AnimationEntityMut,
-    ) -> std::result::Result<(), AnimationEvaluationError> {
-        let hips_bone = entity.id();
-        let node = self.nodes.pop().unwrap();
-        let transformation = self
-            .transformations
-            .entry((hips_bone, node))
-            .or_insert_with(|| {
-                let hips_transformations = HIPS_TRANSFORMATIONS
-                    .lock()
-                    .expect("Failed to lock HIPS_TRANSFORMATIONS");
-                hips_transformations
-                    .get(&(hips_bone, node))
-                    .cloned()
-                    .unwrap()
-            });
-        self.base.commit(entity.reborrow())?;
-        let hips_pos = self.property.get_mut(&mut entity)?;
-        *hips_pos = transformation.transform(*hips_pos);
-        Ok(())
+    fn rest(&self) -> Vec3 {
+        self.dist_rest_g
     }
 }
 
@@ -186,8 +136,14 @@ fn calc_delta(
 
 #[cfg(test)]
 mod tests {
-    use crate::vrma::animation::bone_translation::{calc_delta, calc_scaling};
-    use bevy::math::Vec3;
+    use crate::tests::test_app;
+    use crate::vrma::animation::animation_graph::vrm_animation_graph;
+    use crate::vrma::animation::bone_translation::{
+        HipsTranslationAnimationCurve, Transformation, calc_delta, calc_scaling,
+    };
+    use crate::vrma::animation::retarget::RetargetTransformations;
+    use bevy::animation::{AnimationTarget, AnimationTargetId, animated_field};
+    use bevy::prelude::*;
 
     #[test]
     fn test_scaling() {
@@ -200,4 +156,85 @@ mod tests {
         let delta = calc_delta(Vec3::splat(1.), Vec3::splat(2.));
         assert_eq!(delta, Vec3::splat(-1.));
     }
+
+    /// Plays `additive` as an additive layer at half weight over `base`, and returns the translation of the hips.
+    fn add_half_delta(base: Option<Vec3>) -> Vec3 {
+        let mut app = test_app();
+        app.add_plugins(AnimationPlugin);
+        let target_id = AnimationTargetId::from_name(&Name::new("hips"));
+        let clip = |translation: Vec3| {
+            let mut clip = AnimationClip::default();
+            clip.add_variable_curve_to_target(
+                target_id,
+                VariableCurve(Box::new(HipsTranslationAnimationCurve {
+                    base: Box::new(AnimatableCurve::new(
+                        animated_field!(Transform::translation),
+                        ConstantCurve::new(Interval::EVERYWHERE, translation),
+                    )),
+                })),
+            );
+            clip
+        };
+        let mut clips = app.world_mut().resource_mut::<Assets<AnimationClip>>();
+        let base_clip = clips.add(clip(base.unwrap_or_default()));
+        let additive_clip = clips.add(clip(Vec3::new(0., 2., 2.)));
+        let (mut graph, nodes) = vrm_animation_graph([base_clip, additive_clip]);
+        let base_node = graph.graph.neighbors(graph.root).min().unwrap();
+        graph.remove_edge(base_node, nodes[1]);
+        graph.add_edge(graph.root, nodes[1]);
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let mut player = AnimationPlayer::default();
+        if base.is_some() {
+            player.play(nodes[0]).repeat();
+        }
+        player.play(nodes[1]).set_weight(0.5).repeat();
+        let player = app
+            .world_mut()
+            .spawn((player, AnimationGraphHandle(graph)))
+            .id();
+        // VRM is half the height of VRMA, so the translations are halved.
+        let transformation = Transformation {
+            src_rest_g: Vec3::new(0., 2., 0.),
+            dist_rest_g: Vec3::new(0., 1., 0.),
+        };
+        let hips = app
+            .world_mut()
+            .spawn((
+                Transform::from_translation(transformation.dist_rest_g),
+                AnimationTarget {
+                    id: target_id,
+                    player,
+                },
+                RetargetTransformations(
+                    [(nodes[0], transformation), (nodes[1], transformation)]
+                        .into_iter()
+                        .collect(),
+                ),
+            ))
+            .id();
+        // The animation graph is applied in the next frame after it is added.
+        app.update();
+        app.update();
+
+        app.world().get::<Transform>(hips).unwrap().translation
+    }
+
+    #[test]
+    fn add_hips_translation_delta() {
+        let translation = add_half_delta(Some(Vec3::new(1., 2., 0.)));
+        assert!(
+            translation.abs_diff_eq(Vec3::new(0.5, 1., 0.5), 1e-5),
+            "{translation}"
+        );
+
+        let translation = add_half_delta(None);
+        assert!(
+            translation.abs_diff_eq(Vec3::new(0., 1., 0.5), 1e-5),
+            "{translation}"
+        );
+    }
 }
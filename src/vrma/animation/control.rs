@@ -0,0 +1,206 @@
+//! Controls the playback of the VRMA animation that is being played.
+//!
+//! The animation of VRMA is played by the player on the root bone and the players of each expression,
+//! so these triggers apply the same control to all of them to keep them in sync.
+
+use crate::prelude::ChildSearcher;
+use crate::vrma::animation::vrm_animation_players;
+use crate::vrma::{VrmAnimationNodeIndices, VrmaClip, VrmaClips};
+use bevy::animation::{ActiveAnimation, AnimationPlayer};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// The trigger event to change the playback speed of the Vrma's animation.
+///
+/// A negative speed plays the animation in reverse.
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA.
+#[derive(Event, Debug, Copy, Clone, Reflect)]
+pub struct SetVrmaSpeed(pub f32);
+
+/// The trigger event to pause the Vrma's animation.
+///
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA.
+#[derive(Event, Debug, Copy, Clone, Reflect)]
+pub struct PauseVrma;
+
+/// The trigger event to resume the Vrma's animation paused by [`PauseVrma`].
+///
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA.
+#[derive(Event, Debug, Copy, Clone, Reflect)]
+pub struct ResumeVrma;
+
+/// The trigger event to seek the Vrma's animation.
+///
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA.
+#[derive(Event, Debug, Copy, Clone, Reflect)]
+pub enum SeekVrma {
+    /// Seeks to the time from the start of the animation.
+    Time(Duration),
+    /// Seeks to the position normalized by the duration of the animation.
+    /// `0.0` is the start and `1.0` is the end.
+    Normalized(f32),
+}
+
+pub(super) struct VrmaAnimationControlPlugin;
+
+impl Plugin for VrmaAnimationControlPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<SetVrmaSpeed>()
+            .register_type::<PauseVrma>()
+            .register_type::<ResumeVrma>()
+            .register_type::<SeekVrma>()
+            .add_observer(apply_set_vrma_speed)
+            .add_observer(apply_pause_vrma)
+            .add_observer(apply_resume_vrma)
+            .add_observer(apply_seek_vrma);
+    }
+}
+
+fn apply_set_vrma_speed(
+    trigger: Trigger<SetVrmaSpeed>,
+    mut animations: VrmaActiveAnimations,
+) {
+    let speed = trigger.0;
+    animations.for_each(trigger.target(), |animation, _| {
+        animation.set_speed(speed);
+    });
+}
+
+fn apply_pause_vrma(
+    trigger: Trigger<PauseVrma>,
+    mut animations: VrmaActiveAnimations,
+) {
+    animations.for_each(trigger.target(), |animation, _| {
+        animation.pause();
+    });
+}
+
+fn apply_resume_vrma(
+    trigger: Trigger<ResumeVrma>,
+    mut animations: VrmaActiveAnimations,
+) {
+    animations.for_each(trigger.target(), |animation, _| {
+        animation.resume();
+    });
+}
+
+fn apply_seek_vrma(
+    trigger: Trigger<SeekVrma>,
+    mut animations: VrmaActiveAnimations,
+) {
+    let seek = *trigger.event();
+    animations.for_each(trigger.target(), |animation, clip| {
+        let seconds = match seek {
+            SeekVrma::Time(time) => time.as_secs_f32(),
+            SeekVrma::Normalized(position) => clip.duration.as_secs_f32() * position,
+        };
+        animation.seek_to(seconds);
+    });
+}
+
+#[derive(SystemParam)]
+struct VrmaActiveAnimations<'w, 's> {
+    players: Query<'w, 's, &'static mut AnimationPlayer>,
+    vrmas: Query<
+        'w,
+        's,
+        (
+            &'static ChildOf,
+            &'static VrmAnimationNodeIndices,
+            &'static VrmaClips,
+        ),
+    >,
+    childrens: Query<'w, 's, &'static Children>,
+    searcher: ChildSearcher<'w, 's>,
+}
+
+impl VrmaActiveAnimations<'_, '_> {
+    /// Calls `f` with the active animations of the VRMA in the root bone and expression players.
+    fn for_each(
+        &mut self,
+        vrma: Entity,
+        mut f: impl FnMut(&mut ActiveAnimation, &VrmaClip),
+    ) {
+        let Ok((ChildOf(vrm), node_indices, clips)) = self.vrmas.get(vrma) else {
+            return;
+        };
+        for entity in vrm_animation_players(*vrm, &self.searcher, &self.childrens) {
+            let Ok(mut player) = self.players.get_mut(entity) else {
+                continue;
+            };
+            for (node_index, clip) in node_indices.iter().zip(clips.iter()) {
+                if let Some(animation) = player.animation_mut(*node_index) {
+                    f(animation, clip);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::animation::control::VrmaAnimationControlPlugin;
+    use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+    use bevy::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn control_root_bone_and_expression_players() {
+        let mut app = test_app();
+        app.add_plugins((VrmaAnimationPlayPlugin, VrmaAnimationControlPlugin));
+
+        let node_index = AnimationNodeIndex::new(1);
+        let TestVrm {
+            vrm,
+            root_bone,
+            vrmas,
+        } = spawn_vrm_with_vrmas(&mut app, Handle::default(), &[node_index]);
+        let vrma = vrmas[0];
+        app.world_mut()
+            .entity_mut(vrma)
+            .insert(VrmaClips(vec![VrmaClip {
+                name: None,
+                duration: Duration::from_secs(4),
+                markers: Vec::new(),
+            }]));
+        let expressions_root = app
+            .world_mut()
+            .spawn((Name::new(Vrm::EXPRESSIONS_ROOT), ChildOf(vrm)))
+            .id();
+        let expression = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                AnimationPlayer::default(),
+                ChildOf(expressions_root),
+            ))
+            .id();
+
+        let mut commands = app.world_mut().commands();
+        commands.entity(vrma).trigger(PlayVrma::default());
+        commands.entity(vrma).trigger(SetVrmaSpeed(-0.5));
+        commands.entity(vrma).trigger(PauseVrma);
+        commands.entity(vrma).trigger(SeekVrma::Normalized(0.5));
+        app.update();
+
+        for player in [root_bone, expression] {
+            let player = app.world().get::<AnimationPlayer>(player).unwrap();
+            let animation = player.animation(node_index).unwrap();
+            assert_eq!(animation.speed(), -0.5);
+            assert!(animation.is_paused());
+            assert_eq!(animation.seek_time(), 2.0);
+        }
+
+        app.world_mut().commands().entity(vrma).trigger(ResumeVrma);
+        app.update();
+        let player = app.world().get::<AnimationPlayer>(expression).unwrap();
+        assert!(!player.animation(node_index).unwrap().is_paused());
+    }
+}
@@ -0,0 +1,522 @@
+//! Plays VRMA as a layer that drives only some humanoid groups over the other animations.
+//!
+//! Each humanoid group is registered as a mask group of the [`AnimationGraph`] of VRM,
+//! and the mask and the weight of each graph node are updated from [`VrmaLayer`].
+
+use crate::error::vrm_warn;
+use crate::prelude::{ChildSearcher, Vrm};
+use crate::vrm::VrmBone;
+use crate::vrma::animation::animation_graph::base_node;
+use crate::vrma::animation::vrm_animation_players;
+use crate::vrma::{VrmAnimationNodeIndices, VrmaClipSelector, VrmaClips};
+use bevy::animation::graph::AnimationMask;
+use bevy::animation::{AnimationPlayer, AnimationTarget, RepeatAnimation};
+use bevy::app::{Animation, App, Plugin};
+use bevy::prelude::*;
+
+/// The groups of the humanoid bones and expressions that [`VrmaLayer`] drives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub enum HumanoidGroup {
+    Hips,
+    /// `spine`, `chest` and `upperChest`.
+    Spine,
+    /// `neck`, `head`, `jaw` and the eyes.
+    Head,
+    /// The shoulder, upper arm, lower arm and hand on the left.
+    LeftArm,
+    /// The shoulder, upper arm, lower arm and hand on the right.
+    RightArm,
+    LeftFingers,
+    RightFingers,
+    /// The upper leg, lower leg, foot and toes on the left.
+    LeftLeg,
+    /// The upper leg, lower leg, foot and toes on the right.
+    RightLeg,
+    /// The expressions of the face.
+    Expressions,
+}
+
+impl HumanoidGroup {
+    /// The groups above the hips.
+    pub const UPPER_BODY: [HumanoidGroup; 6] = [
+        HumanoidGroup::Spine,
+        HumanoidGroup::Head,
+        HumanoidGroup::LeftArm,
+        HumanoidGroup::RightArm,
+        HumanoidGroup::LeftFingers,
+        HumanoidGroup::RightFingers,
+    ];
+
+    /// The hips and the legs.
+    pub const LOWER_BODY: [HumanoidGroup; 3] = [
+        HumanoidGroup::Hips,
+        HumanoidGroup::LeftLeg,
+        HumanoidGroup::RightLeg,
+    ];
+
+    /// The fingers of both hands.
+    pub const FINGERS: [HumanoidGroup; 2] =
+        [HumanoidGroup::LeftFingers, HumanoidGroup::RightFingers];
+
+    /// Returns the group of the humanoid bone.
+    pub fn from_bone(bone: &VrmBone) -> Option<Self> {
+        let bone = bone.as_str();
+        let group = match bone {
+            "hips" => Self::Hips,
+            "spine" | "chest" | "upperChest" => Self::Spine,
+            "neck" | "head" | "jaw" | "leftEye" | "rightEye" => Self::Head,
+            "leftShoulder" | "leftUpperArm" | "leftLowerArm" | "leftHand" => Self::LeftArm,
+            "rightShoulder" | "rightUpperArm" | "rightLowerArm" | "rightHand" => Self::RightArm,
+            "leftUpperLeg" | "leftLowerLeg" | "leftFoot" | "leftToes" => Self::LeftLeg,
+            "rightUpperLeg" | "rightLowerLeg" | "rightFoot" | "rightToes" => Self::RightLeg,
+            _ if bone.starts_with("left") => Self::LeftFingers,
+            _ if bone.starts_with("right") => Self::RightFingers,
+            _ => return None,
+        };
+        Some(group)
+    }
+
+    #[inline]
+    fn mask_group(self) -> u32 {
+        self as u32
+    }
+}
+
+/// The component that indicates that the VRMA is played as a layer.
+///
+/// This is inserted into the VRMA entity by [`PlayVrmaLayer`], and removed by [`PlayVrma`](crate::prelude::PlayVrma) and [`StopVrma`](crate::prelude::StopVrma).
+/// You can change the weight of the playing layer by modifying this component.
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaLayer {
+    /// The groups driven by this layer.
+    /// The other groups are not affected by this layer.
+    pub groups: Vec<HumanoidGroup>,
+    /// The weight of this layer.
+    ///
+    /// In [`VrmaLayerMode::Override`], this is the ratio to blend with the animations below it, in `0.0..=1.0`,
+    /// and at `1.0` this layer overrides the animation played by [`PlayVrma`](crate::prelude::PlayVrma) in its groups.
+    ///
+    /// In [`VrmaLayerMode::Additive`], this scales the delta added to the animations below it.
+    pub weight: f32,
+    /// How this layer is combined with the animations below it.
+    pub mode: VrmaLayerMode,
+}
+
+impl Default for VrmaLayer {
+    fn default() -> Self {
+        Self {
+            groups: HumanoidGroup::UPPER_BODY.to_vec(),
+            weight: 1.0,
+            mode: VrmaLayerMode::default(),
+        }
+    }
+}
+
+impl VrmaLayer {
+    fn mask(&self) -> AnimationMask {
+        self.groups
+            .iter()
+            .fold(0, |mask, group| mask | (1 << group.mask_group()))
+    }
+
+    /// The weight of the graph node relative to the animations below, whose total weight is `1.0`.
+    fn node_weight(&self) -> f32 {
+        match self.mode {
+            VrmaLayerMode::Additive => self.weight.max(0.),
+            VrmaLayerMode::Override => {
+                let weight = self.weight.clamp(0., 1.);
+                if weight < 1. {
+                    weight / (1. - weight)
+                } else {
+                    1.
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn overrides(&self) -> bool {
+        self.mode == VrmaLayerMode::Override && 1. <= self.weight
+    }
+}
+
+/// How [`VrmaLayer`] is combined with the animations below it.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub enum VrmaLayerMode {
+    /// Blends the pose of the layer with the animations below it.
+    #[default]
+    Override,
+    /// Adds the difference of the pose from the rest pose of VRMA to the animations below it,
+    /// such as breathing, nodding and recoil.
+    ///
+    /// The rotations and the hips translation are retargeted before taking the difference,
+    /// so the delta matches the proportions of VRM.
+    /// The groups without any animation below are posed by the delta only.
+    Additive,
+}
+
+/// The trigger event to play the Vrma's animation as a layer.
+///
+/// Unlike [`PlayVrma`](crate::prelude::PlayVrma), the other VRMAs are not stopped,
+/// and the animation only drives the groups specified in [`VrmaLayer::groups`].
+///
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_vrm1::prelude::*;
+///
+/// fn wave_right_arm(
+///     trigger: Trigger<LoadedVrma>,
+///     mut commands: Commands,
+/// ) {
+///     commands.entity(trigger.target()).trigger(PlayVrmaLayer {
+///         layer: VrmaLayer {
+///             groups: vec![HumanoidGroup::RightArm, HumanoidGroup::RightFingers],
+///             ..default()
+///         },
+///         ..default()
+///     });
+/// }
+/// ```
+#[derive(Event, Debug, Reflect)]
+pub struct PlayVrmaLayer {
+    /// The animation to play among [`VrmaClips`].
+    /// Default is the first animation.
+    pub clip: VrmaClipSelector,
+
+    pub layer: VrmaLayer,
+
+    /// Repetition behavior of an animation.
+    /// Default is [`RepeatAnimation::Never`].
+    pub repeat: RepeatAnimation,
+
+    /// The playback speed of the animation.
+    /// Default is `1.0`.
+    pub speed: f32,
+}
+
+impl Default for PlayVrmaLayer {
+    fn default() -> Self {
+        Self {
+            clip: VrmaClipSelector::default(),
+            layer: VrmaLayer::default(),
+            repeat: RepeatAnimation::Never,
+            speed: 1.0,
+        }
+    }
+}
+
+pub(super) struct VrmaLayerPlugin;
+
+impl Plugin for VrmaLayerPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<HumanoidGroup>()
+            .register_type::<VrmaLayer>()
+            .register_type::<VrmaLayerMode>()
+            .register_type::<PlayVrmaLayer>()
+            .add_observer(apply_play_vrma_layer)
+            .add_systems(PostUpdate, update_vrma_layers.before(Animation));
+    }
+}
+
+/// Registers the animation targets of the humanoid bones and expressions of VRM to the mask groups.
+pub(crate) fn assign_mask_groups(
+    graph: &mut AnimationGraph,
+    vrm: Entity,
+    searcher: &ChildSearcher,
+    childrens: &Query<&Children>,
+    bones: &Query<(Option<&VrmBone>, &AnimationTarget)>,
+) {
+    if let Some(root_bone) = searcher.find_root_bone(vrm) {
+        for bone in std::iter::once(root_bone).chain(childrens.iter_descendants(root_bone)) {
+            let Ok((Some(vrm_bone), target)) = bones.get(bone) else {
+                continue;
+            };
+            if let Some(group) = HumanoidGroup::from_bone(vrm_bone) {
+                graph.add_target_to_mask_group(target.id, group.mask_group());
+            }
+        }
+    }
+    if let Some(expressions_root) = searcher.find_expressions_root(vrm) {
+        for expression in childrens.iter_descendants(expressions_root) {
+            if let Ok((_, target)) = bones.get(expression) {
+                graph.add_target_to_mask_group(target.id, HumanoidGroup::Expressions.mask_group());
+            }
+        }
+    }
+}
+
+fn apply_play_vrma_layer(
+    trigger: Trigger<PlayVrmaLayer>,
+    mut commands: Commands,
+    mut players: Query<&mut AnimationPlayer>,
+    vrmas: Query<(&ChildOf, &VrmAnimationNodeIndices, &VrmaClips)>,
+    searcher: ChildSearcher,
+    childrens: Query<&Children>,
+) {
+    let vrma = trigger.target();
+    let Ok((ChildOf(vrm), node_indices, clips)) = vrmas.get(vrma) else {
+        return;
+    };
+    let Some(node_index) = clips
+        .index_of(&trigger.clip)
+        .and_then(|index| node_indices.get(index))
+        .copied()
+    else {
+        vrm_warn!("[VRMA] Not found the animation {:?}", trigger.clip);
+        return;
+    };
+    for entity in vrm_animation_players(*vrm, &searcher, &childrens) {
+        if let Ok(mut player) = players.get_mut(entity) {
+            player
+                .start(node_index)
+                .set_repeat(trigger.repeat)
+                .set_speed(trigger.speed);
+        }
+    }
+    // The mask and the weight are applied by `update_vrma_layers`.
+    commands.entity(vrma).insert(trigger.layer.clone());
+}
+
+fn update_vrma_layers(
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    mut players: Query<&mut AnimationPlayer>,
+    mut removed: RemovedComponents<VrmaLayer>,
+    changed: Query<&ChildOf, Or<(Changed<VrmaLayer>, Changed<VrmAnimationNodeIndices>)>>,
+    vrms: Query<Entity, With<Vrm>>,
+    vrmas: Query<(&VrmAnimationNodeIndices, Option<&VrmaLayer>)>,
+    graph_handles: Query<&AnimationGraphHandle>,
+    searcher: ChildSearcher,
+    childrens: Query<&Children>,
+) {
+    // The VRM of the removed layer can't be found if the VRMA has been despawned.
+    let mut targets = if removed.read().count() == 0 {
+        changed.iter().map(ChildOf::parent).collect::<Vec<_>>()
+    } else {
+        vrms.iter().collect()
+    };
+    targets.sort();
+    targets.dedup();
+    for vrm in targets {
+        let Some(graph) = searcher
+            .find_root_bone(vrm)
+            .and_then(|root_bone| graph_handles.get(root_bone).ok())
+            .and_then(|handle| graphs.get_mut(handle))
+        else {
+            continue;
+        };
+        let Ok(children) = childrens.get(vrm) else {
+            continue;
+        };
+        let vrmas = children
+            .iter()
+            .filter_map(|child| vrmas.get(child).ok())
+            .collect::<Vec<_>>();
+        // The animations below are excluded from the groups overridden by the layers.
+        let override_mask = vrmas
+            .iter()
+            .filter_map(|(_, layer)| *layer)
+            .filter(|layer| layer.overrides())
+            .fold(0, |mask, layer| mask | layer.mask());
+        let Some(base) = base_node(graph) else {
+            continue;
+        };
+        let root = graph.root;
+        let player_entities = vrm_animation_players(vrm, &searcher, &childrens);
+        for (node_indices, layer) in vrmas {
+            let mask = layer.map_or(override_mask, |layer| !layer.mask());
+            for node_index in node_indices.iter() {
+                if let Some(node) = graph.get_mut(*node_index) {
+                    node.mask = mask;
+                }
+                // The additive layers are moved under the additive root node, and the others under the base node.
+                let additive = layer.is_some_and(|layer| layer.mode == VrmaLayerMode::Additive);
+                let (parent, other) = if additive { (root, base) } else { (base, root) };
+                if graph.remove_edge(other, *node_index) {
+                    graph.add_edge(parent, *node_index);
+                }
+                let Some(layer) = layer else {
+                    continue;
+                };
+                for entity in player_entities.iter() {
+                    if let Some(animation) = players
+                        .get_mut(*entity)
+                        .ok()
+                        .as_mut()
+                        .and_then(|player| player.animation_mut(*node_index))
+                    {
+                        animation.set_weight(layer.node_weight());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::animation::animation_graph::vrm_animation_graph;
+    use crate::vrma::animation::layer::VrmaLayerPlugin;
+    use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+    use bevy::animation::{AnimationTarget, AnimationTargetId, RepeatAnimation, animated_field};
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    struct Setup {
+        app: App,
+        walk_vrma: Entity,
+        wave_vrma: Entity,
+        leg: Entity,
+        arm: Entity,
+    }
+
+    impl Setup {
+        fn translation(
+            &self,
+            entity: Entity,
+        ) -> Vec3 {
+            self.app
+                .world()
+                .get::<Transform>(entity)
+                .unwrap()
+                .translation
+        }
+
+        fn play(
+            &mut self,
+            layer: VrmaLayer,
+        ) {
+            let mut commands = self.app.world_mut().commands();
+            commands.entity(self.walk_vrma).trigger(PlayVrma {
+                repeat: RepeatAnimation::Forever,
+                ..default()
+            });
+            commands.entity(self.wave_vrma).trigger(PlayVrmaLayer {
+                layer,
+                repeat: RepeatAnimation::Forever,
+                ..default()
+            });
+            // The modified animation graph is applied in the next frame.
+            self.app.update();
+            self.app.update();
+        }
+    }
+
+    fn constant_clip(
+        app: &mut App,
+        translation: Vec3,
+    ) -> Handle<AnimationClip> {
+        let mut clip = AnimationClip::default();
+        for name in ["leg", "arm"] {
+            clip.add_curve_to_target(
+                AnimationTargetId::from_name(&Name::new(name)),
+                AnimatableCurve::new(
+                    animated_field!(Transform::translation),
+                    UnevenSampleAutoCurve::new([(0., translation), (1., translation)]).unwrap(),
+                ),
+            );
+        }
+        app.world_mut()
+            .resource_mut::<Assets<AnimationClip>>()
+            .add(clip)
+    }
+
+    fn setup() -> Setup {
+        let mut app = test_app();
+        app.add_plugins((AnimationPlugin, VrmaAnimationPlayPlugin, VrmaLayerPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )));
+
+        let walk = constant_clip(&mut app, Vec3::X);
+        let wave = constant_clip(&mut app, Vec3::Y);
+        let (mut graph, nodes) = vrm_animation_graph([walk, wave]);
+        let leg_target = AnimationTargetId::from_name(&Name::new("leg"));
+        let arm_target = AnimationTargetId::from_name(&Name::new("arm"));
+        graph.add_target_to_mask_group(leg_target, HumanoidGroup::LeftLeg.mask_group());
+        graph.add_target_to_mask_group(arm_target, HumanoidGroup::RightArm.mask_group());
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let TestVrm {
+            root_bone, vrmas, ..
+        } = spawn_vrm_with_vrmas(&mut app, graph, &nodes);
+        let (walk_vrma, wave_vrma) = (vrmas[0], vrmas[1]);
+        let mut spawn_bone = |name: &str, bone: &str| {
+            app.world_mut()
+                .spawn((
+                    Name::new(name.to_string()),
+                    VrmBone::from(bone),
+                    Transform::default(),
+                    AnimationTarget {
+                        id: AnimationTargetId::from_name(&Name::new(name.to_string())),
+                        player: root_bone,
+                    },
+                    ChildOf(root_bone),
+                ))
+                .id()
+        };
+        let leg = spawn_bone("leg", "leftUpperLeg");
+        let arm = spawn_bone("arm", "rightUpperArm");
+        Setup {
+            app,
+            walk_vrma,
+            wave_vrma,
+            leg,
+            arm,
+        }
+    }
+
+    #[test]
+    fn blend_layer_only_in_masked_groups() {
+        let mut setup = setup();
+        setup.play(VrmaLayer {
+            groups: vec![HumanoidGroup::RightArm],
+            ..default()
+        });
+        assert_eq!(setup.translation(setup.leg), Vec3::X);
+        assert_eq!(setup.translation(setup.arm), Vec3::Y);
+
+        let wave_vrma = setup.wave_vrma;
+        setup
+            .app
+            .world_mut()
+            .get_mut::<VrmaLayer>(wave_vrma)
+            .unwrap()
+            .weight = 0.5;
+        setup.app.update();
+        setup.app.update();
+        assert_eq!(setup.translation(setup.leg), Vec3::X);
+        let arm = setup.translation(setup.arm);
+        assert!(arm.abs_diff_eq(Vec3::new(0.5, 0.5, 0.), 1e-5), "{arm}");
+    }
+
+    #[test]
+    fn add_additive_layer_to_base_animation() {
+        let mut setup = setup();
+        setup.play(VrmaLayer {
+            groups: vec![HumanoidGroup::RightArm],
+            weight: 0.5,
+            mode: VrmaLayerMode::Additive,
+        });
+        assert_eq!(setup.translation(setup.leg), Vec3::X);
+        let arm = setup.translation(setup.arm);
+        assert!(arm.abs_diff_eq(Vec3::new(1., 0.5, 0.), 1e-5), "{arm}");
+    }
+}
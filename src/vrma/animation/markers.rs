@@ -0,0 +1,338 @@
+//! Fires the timed markers of VRMA clips during playback.
+//!
+//! The markers are registered as the events of [`AnimationClip`], so they are also fired when the animation loops
+//! or is seeked with [`SeekVrma`](crate::prelude::SeekVrma).
+//!
+//! The clip fading out during a crossfade keeps firing its markers until the fade finishes.
+//! [`VrmaMarkerReached::weight`] can be used to ignore them.
+
+use crate::error::{vrm_error, vrm_warn};
+use crate::prelude::ChildSearcher;
+use crate::vrma::animation::animation_graph::RequestUpdateAnimationClips;
+use crate::vrma::{VrmAnimationClipHandles, VrmAnimationNodeIndices, VrmaClipSelector, VrmaClips};
+use bevy::app::{App, Plugin};
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// A timed marker in the animation of VRMA.
+///
+/// The markers are read from `extras` of each glTF animation in the following format,
+/// and can also be added with [`AddVrmaMarker`].
+///
+/// ```json
+/// {
+///     "markers": [
+///         { "name": "left_footstep", "time": 0.25 },
+///         { "name": "right_footstep", "time": 0.75 }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", reflect(Serialize, Deserialize))]
+pub struct VrmaMarker {
+    /// The name to identify the marker.
+    pub name: String,
+    /// The time of the marker in seconds from the start of the animation.
+    pub time: f32,
+}
+
+/// The trigger event to add a marker to the animation of VRMA.
+///
+/// You need to emit this via [`Trigger`] with the target entity of the VRMA after [`LoadedVrma`](crate::prelude::LoadedVrma).
+#[derive(Event, Debug, Clone, Reflect)]
+pub struct AddVrmaMarker {
+    /// The animation to add the marker to.
+    pub clip: VrmaClipSelector,
+    pub marker: VrmaMarker,
+}
+
+/// An event that is emitted when the playback of VRMA reaches a [`VrmaMarker`].
+///
+/// This event is emitted as a trigger.
+/// The target of the trigger is the VRM entity.
+#[derive(Event, Debug, Clone, Reflect)]
+pub struct VrmaMarkerReached {
+    pub vrma: Entity,
+    /// The index of the animation in [`VrmaClips`].
+    pub clip: usize,
+    pub marker: VrmaMarker,
+    /// The weight of the animation when the marker is reached.
+    ///
+    /// This is less than `1.0` while the animation is fading in or out during a crossfade,
+    /// so that the markers of the fading out animation can be ignored with a threshold.
+    pub weight: f32,
+}
+
+pub(super) struct VrmaMarkersPlugin;
+
+impl Plugin for VrmaMarkersPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<VrmaMarker>()
+            .register_type::<AddVrmaMarker>()
+            .register_type::<VrmaMarkerReached>()
+            .add_observer(apply_register_markers)
+            .add_observer(apply_add_vrma_marker);
+    }
+}
+
+#[derive(Deserialize)]
+struct AnimationExtras {
+    #[serde(default)]
+    markers: Vec<MarkerExtras>,
+}
+
+/// The format of a marker in `extras`.
+///
+/// This is separated from [`VrmaMarker`] so that its serialization can be enabled by the `serde` feature.
+#[derive(Deserialize)]
+struct MarkerExtras {
+    name: String,
+    time: f32,
+}
+
+impl From<MarkerExtras> for VrmaMarker {
+    fn from(marker: MarkerExtras) -> Self {
+        Self {
+            name: marker.name,
+            time: marker.time,
+        }
+    }
+}
+
+/// Reads the markers from `extras` of each animation in the same order as [`Gltf::animations`].
+pub(crate) fn read_markers(gltf: &Gltf) -> Vec<Vec<VrmaMarker>> {
+    let Some(source) = gltf.source.as_ref() else {
+        return vec![Vec::new(); gltf.animations.len()];
+    };
+    source
+        .animations()
+        .map(|animation| {
+            animation
+                .extras()
+                .as_ref()
+                .map(|extras| parse_markers(extras.get()))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn parse_markers(extras: &str) -> Vec<VrmaMarker> {
+    match serde_json::from_str::<AnimationExtras>(extras) {
+        Ok(extras) => extras.markers.into_iter().map(VrmaMarker::from).collect(),
+        Err(e) => {
+            vrm_error!("[VRMA] Failed to parse the markers of animation", e);
+            Vec::new()
+        }
+    }
+}
+
+fn apply_register_markers(
+    trigger: Trigger<RequestUpdateAnimationClips>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    vrmas: Query<(&ChildOf, &VrmAnimationClipHandles, &VrmaClips)>,
+    searcher: ChildSearcher,
+) {
+    let vrma = trigger.target();
+    let Ok((ChildOf(vrm), handles, vrma_clips)) = vrmas.get(vrma) else {
+        return;
+    };
+    let Some(root_bone) = searcher.find_root_bone(*vrm) else {
+        return;
+    };
+    for (index, (handle, vrma_clip)) in handles.0.iter().zip(vrma_clips.iter()).enumerate() {
+        let Some(clip) = clips.get_mut(handle.id()) else {
+            continue;
+        };
+        for marker in vrma_clip.markers.iter() {
+            register_marker(clip, marker.clone(), *vrm, vrma, index, root_bone);
+        }
+    }
+}
+
+fn apply_add_vrma_marker(
+    trigger: Trigger<AddVrmaMarker>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    mut vrmas: Query<(
+        &ChildOf,
+        &VrmAnimationClipHandles,
+        &mut VrmaClips,
+        Has<VrmAnimationNodeIndices>,
+    )>,
+    searcher: ChildSearcher,
+) {
+    let vrma = trigger.target();
+    let Ok((ChildOf(vrm), handles, mut vrma_clips, registered)) = vrmas.get_mut(vrma) else {
+        vrm_warn!("[VRMA] AddVrmaMarker must be triggered after VRMA is loaded");
+        return;
+    };
+    let Some(index) = vrma_clips.index_of(&trigger.clip) else {
+        vrm_warn!("[VRMA] Not found the animation {:?}", trigger.clip);
+        return;
+    };
+    vrma_clips.0[index].markers.push(trigger.marker.clone());
+    // The markers in `VrmaClips` are registered together when the animation graph of VRM is built.
+    if !registered {
+        return;
+    }
+    let Some(root_bone) = searcher.find_root_bone(*vrm) else {
+        return;
+    };
+    let Some(clip) = handles
+        .0
+        .get(index)
+        .and_then(|handle| clips.get_mut(handle.id()))
+    else {
+        return;
+    };
+    register_marker(clip, trigger.marker.clone(), *vrm, vrma, index, root_bone);
+}
+
+fn register_marker(
+    clip: &mut AnimationClip,
+    marker: VrmaMarker,
+    vrm: Entity,
+    vrma: Entity,
+    index: usize,
+    root_bone: Entity,
+) {
+    if !(0.0..=clip.duration()).contains(&marker.time) {
+        vrm_warn!(
+            "[VRMA] The marker {} is out of the animation duration: {}",
+            marker.name,
+            marker.time
+        );
+        return;
+    }
+    let time = marker.time;
+    clip.add_event_fn(time, move |commands, player, _, weight| {
+        // The expression players also play this clip, so only the player on the root bone fires the marker.
+        if player == root_bone {
+            commands.entity(vrm).trigger(VrmaMarkerReached {
+                vrma,
+                clip: index,
+                marker: marker.clone(),
+                weight,
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::VrmAnimationClipHandles;
+    use crate::vrma::animation::markers::{VrmaMarkersPlugin, parse_markers};
+    use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+    use bevy::animation::RepeatAnimation;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[derive(Resource, Default)]
+    struct Reached(Vec<String>);
+
+    #[test]
+    fn fire_markers_once_per_loop() {
+        let mut app = test_app();
+        app.add_plugins((AnimationPlugin, VrmaAnimationPlayPlugin, VrmaMarkersPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                300,
+            )))
+            .init_resource::<Reached>();
+
+        let mut clip = AnimationClip::default();
+        clip.set_duration(1.);
+        let clip = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationClip>>()
+            .add(clip);
+        let (graph, node_index) = AnimationGraph::from_clip(clip.clone());
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let TestVrm { vrm, vrmas, .. } =
+            spawn_vrm_with_vrmas(&mut app, graph.clone(), &[node_index]);
+        let vrma = vrmas[0];
+        app.world_mut().entity_mut(vrm).observe(
+            |trigger: Trigger<VrmaMarkerReached>, mut reached: ResMut<Reached>| {
+                reached.0.push(trigger.marker.name.clone());
+            },
+        );
+        app.world_mut().entity_mut(vrma).insert((
+            VrmAnimationClipHandles(vec![clip]),
+            VrmaClips(vec![VrmaClip {
+                name: Some("walk".to_string()),
+                duration: Duration::from_secs(1),
+                markers: Vec::new(),
+            }]),
+        ));
+        let expressions_root = app
+            .world_mut()
+            .spawn((Name::new(Vrm::EXPRESSIONS_ROOT), ChildOf(vrm)))
+            .id();
+        app.world_mut().spawn((
+            Transform::default(),
+            AnimationPlayer::default(),
+            AnimationGraphHandle(graph),
+            ChildOf(expressions_root),
+        ));
+
+        let mut commands = app.world_mut().commands();
+        commands.entity(vrma).trigger(AddVrmaMarker {
+            clip: "walk".into(),
+            marker: VrmaMarker {
+                name: "footstep".to_string(),
+                time: 0.5,
+            },
+        });
+        commands.entity(vrma).trigger(PlayVrma {
+            repeat: RepeatAnimation::Count(2),
+            ..default()
+        });
+        for _ in 0..20 {
+            app.update();
+        }
+
+        assert_eq!(
+            app.world().resource::<Reached>().0,
+            ["footstep", "footstep"]
+        );
+    }
+
+    #[test]
+    fn parse_markers_from_extras() {
+        let markers = parse_markers(
+            r#"{
+                "markers": [
+                    { "name": "left_footstep", "time": 0.25 },
+                    { "name": "right_footstep", "time": 0.75 }
+                ],
+                "author": "bevy_vrm1"
+            }"#,
+        );
+        assert_eq!(
+            markers,
+            [
+                VrmaMarker {
+                    name: "left_footstep".to_string(),
+                    time: 0.25,
+                },
+                VrmaMarker {
+                    name: "right_footstep".to_string(),
+                    time: 0.75,
+                },
+            ]
+        );
+        assert!(parse_markers(r#"{ "author": "bevy_vrm1" }"#).is_empty());
+        assert!(parse_markers(r#"{ "markers": [{ "name": "footstep" }] }"#).is_empty());
+        assert!(parse_markers(r#"{ "markers": [ "#).is_empty());
+    }
+}
@@ -1,10 +1,12 @@
+use crate::error::vrm_warn;
 use crate::prelude::ChildSearcher;
-use crate::vrma::VrmAnimationNodeIndex;
+use crate::vrma::animation::layer::VrmaLayer;
+use crate::vrma::{VrmAnimationNodeIndices, VrmaClipSelector, VrmaClips};
 use bevy::animation::{AnimationPlayer, RepeatAnimation};
 use bevy::app::{App, Plugin};
 use bevy::prelude::{
-    AnimationNodeIndex, AnimationTransitions, ChildOf, Children, Entity, Event, Query, Reflect,
-    Transform, Trigger,
+    AnimationNodeIndex, AnimationTransitions, ChildOf, Children, Commands, Entity, Event, Has,
+    Query, Reflect, Transform, Trigger,
 };
 use std::time::Duration;
 
@@ -13,8 +15,13 @@ use std::time::Duration;
 /// You need to emit this via [`Trigger`] with the target entity of the VRMA you want to play the animation on.
 ///
 /// If there are multiple VRMA entities, the animation of all other VRMAs will be stopped except for the one specified in the trigger.
+/// The VRMAs played as layers by [`PlayVrmaLayer`](crate::prelude::PlayVrmaLayer) keep playing over this animation.
 #[derive(Event, Debug, Reflect)]
 pub struct PlayVrma {
+    /// The animation to play among [`VrmaClips`].
+    /// Default is the first animation.
+    pub clip: VrmaClipSelector,
+
     /// Repetition behavior of an animation.
     /// Default is [`RepeatAnimation::Never`].
     pub repeat: RepeatAnimation,
@@ -22,18 +29,29 @@ pub struct PlayVrma {
     /// A time until the existing animation fades out.
     /// Default is 300 milliseconds.
     pub transition_duration: Duration,
+
+    /// The playback speed of the animation.
+    /// A negative speed plays the animation in reverse.
+    /// Default is `1.0`.
+    ///
+    /// The speed can be changed while playing with [`SetVrmaSpeed`](crate::prelude::SetVrmaSpeed).
+    pub speed: f32,
 }
 
 impl Default for PlayVrma {
     fn default() -> Self {
         Self {
+            clip: VrmaClipSelector::default(),
             repeat: RepeatAnimation::Never,
             transition_duration: Duration::from_millis(300),
+            speed: 1.0,
         }
     }
 }
 
 /// The trigger event to stop the Vrma's animation.
+///
+/// If the VRMA is played as a layer, the layer is also removed.
 ///You need to emit this via [`Trigger`] with the target entity of the VRMA you want to stop the animation on.
 #[derive(Event, Debug)]
 pub struct StopVrma;
@@ -53,6 +71,7 @@ impl Plugin for VrmaAnimationPlayPlugin {
 
 fn apply_play_vrma(
     trigger: Trigger<PlayVrma>,
+    mut commands: Commands,
     mut players: Query<(
         &mut Transform,
         &mut AnimationPlayer,
@@ -61,27 +80,49 @@ fn apply_play_vrma(
     searcher: ChildSearcher,
     parents: Query<&ChildOf>,
     childrens: Query<&Children>,
-    vrmas: Query<&VrmAnimationNodeIndex>,
+    vrmas: Query<(&VrmAnimationNodeIndices, &VrmaClips, Has<VrmaLayer>)>,
 ) {
     let vrma_entity = trigger.target();
     let Ok(ChildOf(vrm_entity)) = parents.get(vrma_entity) else {
         return;
     };
-    let Ok(node_index) = vrmas.get(vrma_entity) else {
+    let Ok((node_indices, clips, _)) = vrmas.get(vrma_entity) else {
         return;
     };
+    let Some(node_index) = clips
+        .index_of(&trigger.clip)
+        .and_then(|index| node_indices.get(index))
+        .copied()
+    else {
+        vrm_warn!("[VRMA] Not found the animation {:?}", trigger.clip);
+        return;
+    };
+    // This VRMA is no longer played as a layer even if it was.
+    commands.entity(vrma_entity).remove::<VrmaLayer>();
+    let layer_nodes = childrens
+        .get(*vrm_entity)
+        .into_iter()
+        .flat_map(|children| children.iter().copied())
+        .filter(|child| *child != vrma_entity)
+        .filter_map(|child| vrmas.get(child).ok())
+        .filter(|(_, _, is_layer)| *is_layer)
+        .flat_map(|(node_indices, _, _)| node_indices.iter().copied())
+        .collect::<Vec<_>>();
     play_humanoid_bone_animation(
         *vrm_entity,
-        node_index.0,
+        node_index,
         trigger.repeat,
+        trigger.speed,
         trigger.transition_duration,
         &searcher,
         &mut players,
     );
     play_expression_animations(
         *vrm_entity,
-        node_index.0,
+        node_index,
         trigger.repeat,
+        trigger.speed,
+        &layer_nodes,
         &mut players,
         &childrens,
         &searcher,
@@ -92,6 +133,7 @@ fn play_humanoid_bone_animation(
     vrm: Entity,
     node_index: AnimationNodeIndex,
     repeat: RepeatAnimation,
+    speed: f32,
     transition_duration: Duration,
     searcher: &ChildSearcher,
     players: &mut Query<(
@@ -108,13 +150,16 @@ fn play_humanoid_bone_animation(
     };
     transitions
         .play(&mut player, node_index, transition_duration)
-        .set_repeat(repeat);
+        .set_repeat(repeat)
+        .set_speed(speed);
 }
 
 fn play_expression_animations(
     vrm: Entity,
     node_index: AnimationNodeIndex,
     repeat: RepeatAnimation,
+    speed: f32,
+    layer_nodes: &[AnimationNodeIndex],
     entities: &mut Query<(
         &mut Transform,
         &mut AnimationPlayer,
@@ -133,37 +178,53 @@ fn play_expression_animations(
         if let Ok((mut tf, mut player, _)) = entities.get_mut(child) {
             // Reset the expression weight to zero.
             tf.translation.x = 0.0;
-            player.stop_all();
-            player.play(node_index).set_repeat(repeat);
+            let stopped = player
+                .playing_animations()
+                .map(|(node_index, _)| *node_index)
+                .filter(|node_index| !layer_nodes.contains(node_index))
+                .collect::<Vec<_>>();
+            for node_index in stopped {
+                player.stop(node_index);
+            }
+            player.play(node_index).set_repeat(repeat).set_speed(speed);
         };
     }
 }
 
 fn apply_stop_vrma(
     trigger: Trigger<StopVrma>,
+    mut commands: Commands,
     mut rig_entities: Query<&mut AnimationPlayer>,
-    vrmas: Query<&VrmAnimationNodeIndex>,
+    vrmas: Query<&VrmAnimationNodeIndices>,
+    parents: Query<&ChildOf>,
     rig_children: Query<&Children>,
 ) {
     let vrma_entity = trigger.target();
-    let Ok(node_index) = vrmas.get(vrma_entity) else {
+    let Ok(node_indices) = vrmas.get(vrma_entity) else {
         return;
     };
-    stop_animations(vrma_entity, node_index.0, &mut rig_entities, &rig_children);
+    commands.entity(vrma_entity).remove::<VrmaLayer>();
+    // The animation of VRMA is played by the players of VRM, which is the parent of VRMA.
+    let root = parents
+        .get(vrma_entity)
+        .map_or(vrma_entity, ChildOf::parent);
+    stop_animations(root, node_indices, &mut rig_entities, &rig_children);
 }
 
 fn stop_animations(
     entity: Entity,
-    node_index: AnimationNodeIndex,
+    node_indices: &[AnimationNodeIndex],
     rig_entities: &mut Query<&mut AnimationPlayer>,
     rig_children: &Query<&Children>,
 ) {
     if let Ok(mut player) = rig_entities.get_mut(entity) {
-        player.stop(node_index);
+        for node_index in node_indices {
+            player.stop(*node_index);
+        }
     };
     if let Ok(children) = rig_children.get(entity) {
         for child in children.iter().copied() {
-            stop_animations(child, node_index, rig_entities, rig_children);
+            stop_animations(child, node_indices, rig_entities, rig_children);
         }
     }
 }
@@ -171,27 +232,23 @@ fn stop_animations(
 #[cfg(test)]
 mod tests {
     use crate::prelude::*;
-    use crate::tests::test_app;
-    use crate::vrma::VrmAnimationNodeIndex;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::VrmAnimationNodeIndices;
     use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
     use bevy::prelude::*;
     use bevy_test_helper::system::SystemExt;
+    use std::time::Duration;
 
     #[test]
     fn test_play_vrma() {
         let mut app = test_app();
         app.add_plugins(VrmaAnimationPlayPlugin);
-
-        let vrm = app.world_mut().spawn_empty().id();
-        let vrma = app.world_mut().spawn(VrmAnimationNodeIndex::default()).id();
-        app.world_mut().commands().entity(vrm).add_child(vrma);
-
-        app.world_mut().commands().entity(vrm).with_child((
-            Name::new(Vrm::ROOT_BONE),
-            Transform::default(),
-            AnimationPlayer::default(),
-            AnimationTransitions::default(),
-        ));
+        let TestVrm { vrmas, .. } = spawn_vrm_with_vrmas(
+            &mut app,
+            Handle::default(),
+            &[AnimationNodeIndex::default()],
+        );
+        let vrma = vrmas[0];
 
         app.world_mut()
             .commands()
@@ -206,20 +263,48 @@ mod tests {
     }
 
     #[test]
-    fn test_stop_vrma() {
+    fn play_vrma_clip_by_name() {
         let mut app = test_app();
         app.add_plugins(VrmaAnimationPlayPlugin);
 
-        let vrm = app.world_mut().spawn_empty().id();
-        let vrma = app.world_mut().spawn(VrmAnimationNodeIndex::default()).id();
-        app.world_mut().commands().entity(vrm).add_child(vrma);
-
-        app.world_mut().commands().entity(vrm).with_child((
-            Name::new(Vrm::ROOT_BONE),
-            AnimationPlayer::default(),
-            AnimationTransitions::default(),
+        let TestVrm { vrmas, .. } =
+            spawn_vrm_with_vrmas(&mut app, Handle::default(), &[AnimationNodeIndex::new(1)]);
+        let vrma = vrmas[0];
+        let clip = |name: &str| VrmaClip {
+            name: Some(name.to_string()),
+            duration: Duration::from_secs(1),
+            markers: Vec::new(),
+        };
+        app.world_mut().entity_mut(vrma).insert((
+            VrmAnimationNodeIndices(vec![AnimationNodeIndex::new(1), AnimationNodeIndex::new(2)]),
+            VrmaClips(vec![clip("idle"), clip("walk")]),
         ));
 
+        app.world_mut().commands().entity(vrma).trigger(PlayVrma {
+            clip: "walk".into(),
+            ..default()
+        });
+        app.update();
+
+        app.run_system_once(|player: Query<&AnimationPlayer>| {
+            let player = player.single().expect("Failed to find AnimationPlayer");
+            assert!(!player.is_playing_animation(AnimationNodeIndex::new(1)));
+            assert!(player.is_playing_animation(AnimationNodeIndex::new(2)));
+        });
+    }
+
+    #[test]
+    fn test_stop_vrma() {
+        let mut app = test_app();
+        app.add_plugins(VrmaAnimationPlayPlugin);
+
+        let TestVrm { vrmas, .. } = spawn_vrm_with_vrmas(
+            &mut app,
+            Handle::default(),
+            &[AnimationNodeIndex::default()],
+        );
+        let vrma = vrmas[0];
+
         app.world_mut()
             .commands()
             .entity(vrma)
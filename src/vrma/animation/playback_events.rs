@@ -0,0 +1,199 @@
+//! Emits the triggers when the animation of VRMA finishes or loops.
+
+use crate::prelude::ChildSearcher;
+use crate::vrma::VrmAnimationNodeIndices;
+use crate::vrma::animation::vrm_animation_players;
+use bevy::animation::AnimationPlayer;
+use bevy::app::{Animation, App, Plugin};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+/// An event that is emitted when the animation of VRMA finishes.
+///
+/// This event is emitted as a trigger.
+/// The target of the trigger is the VRMA entity.
+#[derive(Debug, Event, Copy, Clone, Reflect)]
+pub struct VrmaFinished {
+    pub vrm: Entity,
+    /// The index of the finished animation in [`VrmaClips`](crate::prelude::VrmaClips).
+    pub clip: usize,
+}
+
+/// An event that is emitted each time the animation of VRMA loops.
+///
+/// This event is emitted as a trigger.
+/// The target of the trigger is the VRMA entity.
+#[derive(Debug, Event, Copy, Clone, Reflect)]
+pub struct VrmaLooped {
+    pub vrm: Entity,
+    /// The index of the looped animation in [`VrmaClips`](crate::prelude::VrmaClips).
+    pub clip: usize,
+}
+
+pub(super) struct VrmaPlaybackEventsPlugin;
+
+impl Plugin for VrmaPlaybackEventsPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<VrmaFinished>()
+            .register_type::<VrmaLooped>()
+            .add_systems(PostUpdate, trigger_playback_events.after(Animation));
+    }
+}
+
+/// The playback states of the animations of VRMA observed in the previous frame.
+#[derive(Component, Debug, Default, Deref, DerefMut)]
+struct VrmaPlaybackStates(HashMap<AnimationNodeIndex, PlaybackState>);
+
+#[derive(Debug, Default, Copy, Clone)]
+struct PlaybackState {
+    completions: u32,
+    finished: bool,
+}
+
+fn trigger_playback_events(
+    mut commands: Commands,
+    mut vrmas: Query<(
+        Entity,
+        &ChildOf,
+        &VrmAnimationNodeIndices,
+        Option<&mut VrmaPlaybackStates>,
+    )>,
+    players: Query<&AnimationPlayer>,
+    searcher: ChildSearcher,
+    childrens: Query<&Children>,
+) {
+    for (vrma, ChildOf(vrm), node_indices, previous_states) in vrmas.iter_mut() {
+        let players = vrm_animation_players(*vrm, &searcher, &childrens)
+            .into_iter()
+            .filter_map(|entity| players.get(entity).ok())
+            .collect::<Vec<_>>();
+        let mut states = VrmaPlaybackStates::default();
+        for (clip, node_index) in node_indices.iter().enumerate() {
+            let Some(state) = playback_state(&players, *node_index) else {
+                continue;
+            };
+            let previous = previous_states
+                .as_ref()
+                .and_then(|states| states.get(node_index))
+                .copied()
+                .unwrap_or_default();
+            // The last completion of a finite animation is reported as finished instead of looped.
+            let loops = if state.finished {
+                state.completions.saturating_sub(1)
+            } else {
+                state.completions
+            };
+            for _ in previous.completions..loops {
+                commands
+                    .entity(vrma)
+                    .trigger(VrmaLooped { vrm: *vrm, clip });
+            }
+            if state.finished && !previous.finished {
+                commands
+                    .entity(vrma)
+                    .trigger(VrmaFinished { vrm: *vrm, clip });
+            }
+            states.insert(*node_index, state);
+        }
+        match previous_states {
+            Some(mut previous_states) => *previous_states = states,
+            None => {
+                commands.entity(vrma).insert(states);
+            }
+        }
+    }
+}
+
+/// Returns the playback state of the animation over the bone and expression players.
+///
+/// The animation is finished only after all players have finished it.
+fn playback_state(
+    players: &[&AnimationPlayer],
+    node_index: AnimationNodeIndex,
+) -> Option<PlaybackState> {
+    players
+        .iter()
+        .filter_map(|player| player.animation(node_index))
+        .map(|animation| PlaybackState {
+            completions: animation.completions(),
+            finished: animation.is_finished(),
+        })
+        .reduce(|s1, s2| PlaybackState {
+            completions: s1.completions.max(s2.completions),
+            finished: s1.finished && s2.finished,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+    use crate::vrma::animation::playback_events::VrmaPlaybackEventsPlugin;
+    use bevy::animation::{AnimationTargetId, RepeatAnimation, animated_field};
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[derive(Resource, Default)]
+    struct Counts {
+        looped: usize,
+        finished: usize,
+    }
+
+    #[test]
+    fn trigger_looped_and_finished_once_per_occurrence() {
+        let mut app = test_app();
+        app.add_plugins((
+            AnimationPlugin,
+            VrmaAnimationPlayPlugin,
+            VrmaPlaybackEventsPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            300,
+        )))
+        .init_resource::<Counts>()
+        .add_observer(|_: Trigger<VrmaLooped>, mut counts: ResMut<Counts>| {
+            counts.looped += 1;
+        })
+        .add_observer(|_: Trigger<VrmaFinished>, mut counts: ResMut<Counts>| {
+            counts.finished += 1;
+        });
+
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_target(
+            AnimationTargetId::from_name(&Name::new("bone")),
+            AnimatableCurve::new(
+                animated_field!(Transform::translation),
+                UnevenSampleAutoCurve::new([(0., Vec3::ZERO), (1., Vec3::ONE)]).unwrap(),
+            ),
+        );
+        let clip = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationClip>>()
+            .add(clip);
+        let (graph, node_index) = AnimationGraph::from_clip(clip);
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+
+        let TestVrm { vrmas, .. } = spawn_vrm_with_vrmas(&mut app, graph, &[node_index]);
+        let vrma = vrmas[0];
+
+        app.world_mut().commands().entity(vrma).trigger(PlayVrma {
+            repeat: RepeatAnimation::Count(2),
+            ..default()
+        });
+        for _ in 0..20 {
+            app.update();
+        }
+
+        let counts = app.world().resource::<Counts>();
+        assert_eq!(counts.looped, 1);
+        assert_eq!(counts.finished, 1);
+    }
+}
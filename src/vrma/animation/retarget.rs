@@ -0,0 +1,310 @@
+use bevy::animation::animatable::{Animatable, BlendInput};
+use bevy::animation::{AnimationEntityMut, AnimationEvaluationError};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use std::any::TypeId;
+
+/// The transformation that retargets the pose sampled from a VRMA to the bone of VRM.
+pub(crate) trait RetargetTransformation: Copy + Send + Sync + 'static {
+    type Value: Animatable + Copy;
+
+    fn transform(
+        &self,
+        src_pose: Self::Value,
+    ) -> Self::Value;
+
+    /// Returns the difference of the retargeted pose from the rest pose of the bone of VRM.
+    ///
+    /// This is used instead of [`RetargetTransformation::transform`] for the animations added by the additive layers.
+    fn delta(
+        &self,
+        src_pose: Self::Value,
+    ) -> Self::Value;
+
+    /// Returns the rest pose of the bone of VRM, to which the delta is added when no animation is below it.
+    fn rest(&self) -> Self::Value;
+}
+
+/// The transformations of the bone of VRM, keyed by the animation graph node of each VRMA.
+///
+/// This component is inserted into the bone entities when the VRMA is loaded,
+/// the entries follow the new nodes when the animation graph is rebuilt,
+/// and the entries of the VRMA are removed when it is despawned.
+#[derive(Component, Debug, Deref, DerefMut)]
+pub(crate) struct RetargetTransformations<T: RetargetTransformation>(
+    pub HashMap<AnimationNodeIndex, T>,
+);
+
+impl<T: RetargetTransformation> Default for RetargetTransformations<T> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+/// Registers the transformation of the VRMA to the bone.
+pub(crate) fn register_retarget_transformation<T: RetargetTransformation>(
+    commands: &mut Commands,
+    bone: Entity,
+    graph_node: AnimationNodeIndex,
+    transformation: T,
+) {
+    commands
+        .entity(bone)
+        .entry::<RetargetTransformations<T>>()
+        .or_default()
+        .and_modify(move |mut transformations| {
+            transformations.insert(graph_node, transformation);
+        });
+}
+
+/// An [`AnimationCurveEvaluator`] that retargets the sampled values per animation graph node before blending them.
+///
+/// Each graph node can have a different transformation because each VRMA has its own rest pose,
+/// so blending the raw values and retargeting the result once produces wrong poses during transitions.
+/// Since the sampled values can only be read by committing them to the entity,
+/// the operations of the animation graph are recorded and replayed in [`AnimationCurveEvaluator::commit`].
+pub(crate) struct RetargetCurveEvaluator<T: RetargetTransformation> {
+    sampler: Box<dyn AnimationCurveEvaluator>,
+    property: Box<dyn AnimatableProperty<Property = T::Value>>,
+    operations: Vec<Operation>,
+}
+
+enum Operation {
+    Sample {
+        weight: f32,
+        graph_node: AnimationNodeIndex,
+    },
+    Blend(AnimationNodeIndex),
+    Add(AnimationNodeIndex),
+    PushBlendRegister {
+        weight: f32,
+        graph_node: AnimationNodeIndex,
+    },
+}
+
+impl<T: RetargetTransformation> RetargetCurveEvaluator<T> {
+    pub fn new(
+        sampler: Box<dyn AnimationCurveEvaluator>,
+        property: Box<dyn AnimatableProperty<Property = T::Value>>,
+    ) -> Self {
+        Self {
+            sampler,
+            property,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Samples the base curve into the sampler and records it to be retargeted at commit.
+    pub fn sample(
+        &mut self,
+        base: &dyn AnimationCurve,
+        t: f32,
+        weight: f32,
+        graph_node: AnimationNodeIndex,
+    ) -> Result<(), AnimationEvaluationError> {
+        base.apply(&mut *self.sampler, t, weight, graph_node)?;
+        self.operations
+            .push(Operation::Sample { weight, graph_node });
+        Ok(())
+    }
+}
+
+impl<T: RetargetTransformation> AnimationCurveEvaluator for RetargetCurveEvaluator<T> {
+    fn blend(
+        &mut self,
+        graph_node: AnimationNodeIndex,
+    ) -> Result<(), AnimationEvaluationError> {
+        self.operations.push(Operation::Blend(graph_node));
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        graph_node: AnimationNodeIndex,
+    ) -> Result<(), AnimationEvaluationError> {
+        self.operations.push(Operation::Add(graph_node));
+        Ok(())
+    }
+
+    fn push_blend_register(
+        &mut self,
+        weight: f32,
+        graph_node: AnimationNodeIndex,
+    ) -> Result<(), AnimationEvaluationError> {
+        self.operations
+            .push(Operation::PushBlendRegister { weight, graph_node });
+        Ok(())
+    }
+
+    fn commit(
+        &mut self,
+        mut entity: AnimationEntityMut,
+    ) -> Result<(), AnimationEvaluationError> {
+        let operations = std::mem::take(&mut self.operations);
+        let sample_count = operations
+            .iter()
+            .filter(|operation| matches!(operation, Operation::Sample { .. }))
+            .count();
+        // The sampler pops its stack on each commit, so the values are read in the reverse order.
+        let mut samples = Vec::with_capacity(sample_count);
+        for _ in 0..sample_count {
+            self.sampler.commit(entity.reborrow())?;
+            samples.push(*self.property.get_mut(&mut entity)?);
+        }
+
+        // The clips under the additive blend node are added to the others as the deltas from the rest pose.
+        let additive_nodes = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Add(graph_node) => Some(*graph_node),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // All transformations of the bone share the rest pose of VRM.
+        let rest = entity
+            .get::<RetargetTransformations<T>>()
+            .and_then(|transformations| transformations.values().next())
+            .map(RetargetTransformation::rest);
+        let mut blender = Blender::<T::Value>::new(rest);
+        for operation in operations {
+            match operation {
+                Operation::Sample { weight, graph_node } => {
+                    let Some(value) = samples.pop() else {
+                        return Err(inconsistent::<Self>());
+                    };
+                    // The bones without the transformation, such as the ones not in the humanoid, are not retargeted.
+                    let transformation = entity
+                        .get::<RetargetTransformations<T>>()
+                        .and_then(|transformations| transformations.get(&graph_node))
+                        .copied();
+                    let delta = transformation.is_some() && additive_nodes.contains(&graph_node);
+                    let value = transformation.map_or(value, |transformation| {
+                        if delta {
+                            transformation.delta(value)
+                        } else {
+                            transformation.transform(value)
+                        }
+                    });
+                    blender.stack.push(StackElement {
+                        value,
+                        weight,
+                        graph_node,
+                        delta,
+                    });
+                }
+                Operation::Blend(graph_node) => blender.combine(graph_node, false),
+                Operation::Add(graph_node) => blender.combine(graph_node, true),
+                Operation::PushBlendRegister { weight, graph_node } => {
+                    blender.push_blend_register(weight, graph_node);
+                }
+            }
+        }
+        let top = blender.stack.pop().ok_or_else(inconsistent::<Self>)?;
+        *self.property.get_mut(&mut entity)? = top.value;
+        Ok(())
+    }
+}
+
+fn inconsistent<T: 'static>() -> AnimationEvaluationError {
+    AnimationEvaluationError::InconsistentEvaluatorImplementation(TypeId::of::<T>())
+}
+
+/// The same blending as the evaluator of [`AnimatableCurve`].
+struct Blender<A: Animatable + Copy> {
+    stack: Vec<StackElement<A>>,
+    blend_register: Option<(A, f32)>,
+    rest: Option<A>,
+}
+
+struct StackElement<A: Animatable> {
+    value: A,
+    weight: f32,
+    graph_node: AnimationNodeIndex,
+    /// Whether the value is the delta from the rest pose sampled by an additive layer.
+    delta: bool,
+}
+
+impl<A: Animatable + Copy> Blender<A> {
+    fn new(rest: Option<A>) -> Self {
+        Self {
+            stack: Vec::new(),
+            blend_register: None,
+            rest,
+        }
+    }
+
+    fn combine(
+        &mut self,
+        graph_node: AnimationNodeIndex,
+        additive: bool,
+    ) {
+        if self
+            .stack
+            .last()
+            .is_none_or(|element| element.graph_node != graph_node)
+        {
+            return;
+        }
+        let StackElement {
+            value,
+            weight,
+            delta,
+            ..
+        } = self.stack.pop().unwrap();
+        self.blend_register = Some(match self.blend_register.take() {
+            None if additive => {
+                // Without any animation below, the delta is added to the rest pose of VRM.
+                let rest = self.rest.filter(|_| delta).map(|rest| BlendInput {
+                    weight: 1.0,
+                    value: rest,
+                    additive: true,
+                });
+                let input = BlendInput {
+                    weight,
+                    value,
+                    additive: true,
+                };
+                (A::blend(rest.into_iter().chain([input])), weight)
+            }
+            None => (value, weight),
+            Some((current_value, current_weight)) => {
+                let current_weight = current_weight + weight;
+                let current_value = if additive {
+                    A::blend(
+                        [
+                            BlendInput {
+                                weight: 1.0,
+                                value: current_value,
+                                additive: true,
+                            },
+                            BlendInput {
+                                weight,
+                                value,
+                                additive: true,
+                            },
+                        ]
+                        .into_iter(),
+                    )
+                } else {
+                    A::interpolate(&current_value, &value, weight / current_weight)
+                };
+                (current_value, current_weight)
+            }
+        });
+    }
+
+    fn push_blend_register(
+        &mut self,
+        weight: f32,
+        graph_node: AnimationNodeIndex,
+    ) {
+        if let Some((value, _)) = self.blend_register.take() {
+            self.stack.push(StackElement {
+                value,
+                weight,
+                graph_node,
+                delta: false,
+            });
+        }
+    }
+}
@@ -0,0 +1,844 @@
+//! Runs the declarative animation state machine of VRM.
+//!
+//! The clip states are played with [`PlayVrma`], so the crossfades between them are done by [`AnimationTransitions`] on the root bone.
+//! The blend space states are played by spawning [`VrmaBlendSpace`], whose weight is faded in and out by the state machine.
+
+mod asset;
+
+use crate::error::vrm_warn;
+use crate::prelude::{
+    ChildSearcher, PlayVrma, StopVrma, VrmaBlendSample, VrmaBlendSpace, VrmaPath,
+};
+use crate::vrma::animation::blend_space::VrmaBlendSpaceTime;
+use crate::vrma::{VrmAnimationNodeIndices, VrmaClips};
+use bevy::animation::{AnimationPlayer, RepeatAnimation};
+use bevy::app::{App, Plugin};
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use std::path::Path;
+use std::time::Duration;
+
+pub use asset::{
+    VrmaCondition, VrmaMotion, VrmaMotionSample, VrmaState, VrmaStateMachine, VrmaStateTransition,
+};
+
+/// The component to run [`VrmaStateMachine`] on the VRM entity.
+///
+/// The VRMAs referenced by the state machine must be spawned as the children of the VRM.
+/// The state machine starts after the VRMAs of the initial state are loaded.
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct VrmaStateMachineHandle(pub Handle<VrmaStateMachine>);
+
+/// The parameters of [`VrmaStateMachine`] that are referenced by [`VrmaCondition`].
+///
+/// This component is inserted into the VRM entity with [`VrmaStateMachineHandle`] if not present.
+/// The unset parameters are treated as `0.0` or `false`.
+#[derive(Component, Debug, Default, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VrmaStateMachineParameters {
+    floats: HashMap<String, f32>,
+    bools: HashMap<String, bool>,
+    triggers: HashSet<String>,
+}
+
+impl VrmaStateMachineParameters {
+    /// Sets the float parameter.
+    pub fn set_float(
+        &mut self,
+        name: impl Into<String>,
+        value: f32,
+    ) {
+        self.floats.insert(name.into(), value);
+    }
+
+    /// Returns the float parameter, or `0.0` if not set.
+    pub fn float(
+        &self,
+        name: &str,
+    ) -> f32 {
+        self.floats.get(name).copied().unwrap_or_default()
+    }
+
+    /// Sets the bool parameter.
+    pub fn set_bool(
+        &mut self,
+        name: impl Into<String>,
+        value: bool,
+    ) {
+        self.bools.insert(name.into(), value);
+    }
+
+    /// Returns the bool parameter, or `false` if not set.
+    pub fn bool(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.bools.get(name).copied().unwrap_or_default()
+    }
+
+    /// Sets the trigger, which is reset when a transition with it is taken.
+    pub fn set_trigger(
+        &mut self,
+        name: impl Into<String>,
+    ) {
+        self.triggers.insert(name.into());
+    }
+
+    /// Resets the trigger without taking a transition.
+    pub fn reset_trigger(
+        &mut self,
+        name: &str,
+    ) {
+        self.triggers.remove(name);
+    }
+
+    /// Returns whether the trigger is set.
+    pub fn is_triggered(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.triggers.contains(name)
+    }
+
+    fn satisfies(
+        &self,
+        condition: &VrmaCondition,
+    ) -> bool {
+        match condition {
+            VrmaCondition::Greater(name, value) => *value < self.float(name),
+            VrmaCondition::Less(name, value) => self.float(name) < *value,
+            VrmaCondition::True(name) => self.bool(name),
+            VrmaCondition::False(name) => !self.bool(name),
+            VrmaCondition::Trigger(name) => self.is_triggered(name),
+        }
+    }
+}
+
+/// The current state of [`VrmaStateMachine`].
+///
+/// This component is inserted into the VRM entity when the state machine starts.
+#[derive(Component, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VrmaStateMachineState {
+    current: String,
+    /// The blend space spawned for the current state.
+    blend_space: Option<Entity>,
+    fade: Option<Fade>,
+}
+
+impl VrmaStateMachineState {
+    /// Returns the name of the current state.
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+}
+
+/// The crossfade from the previous state that [`AnimationTransitions`] can't handle.
+#[derive(Debug, Reflect)]
+struct Fade {
+    from: Motion,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// The motion being played in a state.
+#[derive(Debug, Copy, Clone, Reflect)]
+enum Motion {
+    Clip(Entity),
+    BlendSpace(Entity),
+}
+
+pub(super) struct VrmaStateMachinePlugin;
+
+impl Plugin for VrmaStateMachinePlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.register_type::<VrmaStateMachineHandle>()
+            .register_type::<VrmaStateMachineParameters>()
+            .register_type::<VrmaStateMachineState>()
+            .add_plugins(asset::VrmaStateMachineAssetPlugin)
+            .add_observer(stop_state_machine)
+            .add_systems(Update, run_state_machines);
+    }
+}
+
+fn run_state_machines(
+    mut commands: Commands,
+    mut vrms: Query<(
+        Entity,
+        &VrmaStateMachineHandle,
+        Option<&mut VrmaStateMachineParameters>,
+        Option<&mut VrmaStateMachineState>,
+    )>,
+    mut blend_spaces: Query<(&mut VrmaBlendSpace, Option<&VrmaBlendSpaceTime>)>,
+    state_machines: Res<Assets<VrmaStateMachine>>,
+    vrmas: Query<(
+        Entity,
+        &ChildOf,
+        &VrmaPath,
+        &VrmAnimationNodeIndices,
+        &VrmaClips,
+    )>,
+    players: Query<&AnimationPlayer>,
+    searcher: ChildSearcher,
+    time: Res<Time>,
+) {
+    for (vrm, handle, parameters, state) in vrms.iter_mut() {
+        let Some(state_machine) = state_machines.get(handle.0.id()) else {
+            continue;
+        };
+        let Some(mut parameters) = parameters else {
+            commands
+                .entity(vrm)
+                .insert(VrmaStateMachineParameters::default());
+            continue;
+        };
+        let find_vrma = |path: &Path| {
+            vrmas
+                .iter()
+                .find(|(_, ChildOf(parent), vrma_path, ..)| *parent == vrm && vrma_path.0 == path)
+                .map(|(entity, ..)| entity)
+        };
+        let Some(mut state) = state else {
+            let Some(initial) = state_machine.state(&state_machine.initial_state) else {
+                vrm_warn!(
+                    "[VRMA] Not found the initial state {}",
+                    state_machine.initial_state
+                );
+                continue;
+            };
+            if !motion_vrmas(&initial.motion).all(|path| find_vrma(path).is_some()) {
+                continue;
+            }
+            let blend_space =
+                enter_state(&mut commands, vrm, initial, &parameters, 0., 1., &find_vrma);
+            commands.entity(vrm).insert(VrmaStateMachineState {
+                current: initial.name.clone(),
+                blend_space,
+                fade: None,
+            });
+            continue;
+        };
+
+        update_fade(
+            &mut commands,
+            &mut state,
+            &mut blend_spaces,
+            time.delta_secs(),
+        );
+        let Some(current) = state_machine.state(&state.current) else {
+            continue;
+        };
+        if let (VrmaMotion::BlendSpace { x, y, .. }, Some(Ok((mut blend_space, _)))) = (
+            &current.motion,
+            state.blend_space.map(|entity| blend_spaces.get_mut(entity)),
+        ) {
+            blend_space.parameter = blend_space_parameter(&parameters, x, y.as_deref());
+        }
+        let normalized_time = match (&current.motion, state.blend_space) {
+            (_, Some(blend_space)) => blend_spaces
+                .get(blend_space)
+                .ok()
+                .and_then(|(_, time)| time)
+                .map(|time| time.0)
+                .unwrap_or_default(),
+            (VrmaMotion::Clip { vrma, clip, .. }, None) => find_vrma(vrma)
+                .and_then(|vrma| vrmas.get(vrma).ok())
+                .and_then(|(_, _, _, node_indices, clips)| {
+                    let index = clips.index_of(clip)?;
+                    let root_bone = searcher.find_root_bone(vrm)?;
+                    let animation = players
+                        .get(root_bone)
+                        .ok()?
+                        .animation(node_indices[index])?;
+                    Some(normalized_clip_time(
+                        animation.completions(),
+                        animation.is_finished(),
+                        animation.seek_time(),
+                        clips[index].duration.as_secs_f32(),
+                    ))
+                })
+                .unwrap_or_default(),
+            _ => 0.,
+        };
+        let Some(transition) = state_machine.transitions.iter().find(|transition| {
+            let from = match transition.from.as_ref() {
+                Some(from) => *from == state.current,
+                None => transition.to != state.current,
+            };
+            from && transition
+                .exit_time
+                .is_none_or(|exit_time| exit_time <= normalized_time)
+                && transition
+                    .conditions
+                    .iter()
+                    .all(|condition| parameters.satisfies(condition))
+        }) else {
+            continue;
+        };
+        let Some(next) = state_machine.state(&transition.to) else {
+            vrm_warn!("[VRMA] Not found the state {}", transition.to);
+            continue;
+        };
+        for condition in transition.conditions.iter() {
+            if let VrmaCondition::Trigger(name) = condition {
+                parameters.reset_trigger(name);
+            }
+        }
+        transition_to(
+            &mut commands,
+            vrm,
+            &mut state,
+            current,
+            next,
+            transition.duration,
+            &parameters,
+            &mut blend_spaces,
+            &find_vrma,
+        );
+    }
+}
+
+fn transition_to(
+    commands: &mut Commands,
+    vrm: Entity,
+    state: &mut VrmaStateMachineState,
+    current: &VrmaState,
+    next: &VrmaState,
+    duration: f32,
+    parameters: &VrmaStateMachineParameters,
+    blend_spaces: &mut Query<(&mut VrmaBlendSpace, Option<&VrmaBlendSpaceTime>)>,
+    find_vrma: &impl Fn(&Path) -> Option<Entity>,
+) {
+    // The previous crossfade is completed immediately.
+    if let Some(fade) = state.fade.take() {
+        finish_fade(commands, fade.from);
+    }
+    let from = match (&current.motion, state.blend_space) {
+        (_, Some(blend_space)) => Some(Motion::BlendSpace(blend_space)),
+        (VrmaMotion::Clip { vrma, .. }, None) => find_vrma(vrma).map(Motion::Clip),
+        _ => None,
+    };
+    let clip_to_clip =
+        matches!(from, Some(Motion::Clip(_))) && matches!(next.motion, VrmaMotion::Clip { .. });
+    let fade = from.is_some() && !clip_to_clip && 0. < duration;
+    let blend_space = enter_state(
+        commands,
+        vrm,
+        next,
+        parameters,
+        // The crossfade between clips is done by `AnimationTransitions`.
+        if clip_to_clip || from.is_none() {
+            duration
+        } else {
+            0.
+        },
+        // The blend space fades in from zero, since it is spawned after the weights are applied below.
+        if fade { 0. } else { 1. },
+        find_vrma,
+    );
+    state.current = next.name.clone();
+    state.blend_space = blend_space;
+    state.fade = match from {
+        Some(from) if fade => Some(Fade {
+            from,
+            elapsed: 0.,
+            duration,
+        }),
+        Some(from) if !clip_to_clip => {
+            finish_fade(commands, from);
+            None
+        }
+        _ => None,
+    };
+    if let Some(fade) = state.fade.as_ref() {
+        apply_fade_weights(state.blend_space, fade, blend_spaces);
+    }
+}
+
+/// Starts the motion of the state, and returns the entity of the blend space if spawned.
+fn enter_state(
+    commands: &mut Commands,
+    vrm: Entity,
+    state: &VrmaState,
+    parameters: &VrmaStateMachineParameters,
+    transition_duration: f32,
+    blend_space_weight: f32,
+    find_vrma: &impl Fn(&Path) -> Option<Entity>,
+) -> Option<Entity> {
+    match &state.motion {
+        VrmaMotion::Clip { vrma, clip, repeat } => {
+            let Some(vrma) = find_vrma(vrma) else {
+                vrm_warn!("[VRMA] Not found the VRMA {}", vrma.display());
+                return None;
+            };
+            commands.entity(vrma).trigger(PlayVrma {
+                clip: clip.clone(),
+                repeat: if *repeat {
+                    RepeatAnimation::Forever
+                } else {
+                    RepeatAnimation::Never
+                },
+                // The state machine may be built in code without the validation of the loader.
+                transition_duration: Duration::try_from_secs_f32(transition_duration)
+                    .unwrap_or_default(),
+                speed: state.speed,
+            });
+            None
+        }
+        VrmaMotion::BlendSpace { samples, x, y } => {
+            let samples = samples
+                .iter()
+                .filter_map(|sample| {
+                    let Some(vrma) = find_vrma(&sample.vrma) else {
+                        vrm_warn!("[VRMA] Not found the VRMA {}", sample.vrma.display());
+                        return None;
+                    };
+                    Some(VrmaBlendSample {
+                        vrma,
+                        clip: sample.clip.clone(),
+                        position: Vec2::new(sample.position.0, sample.position.1),
+                    })
+                })
+                .collect();
+            let blend_space = commands
+                .spawn((
+                    Name::new(state.name.clone()),
+                    VrmaBlendSpace {
+                        samples,
+                        parameter: blend_space_parameter(parameters, x, y.as_deref()),
+                        speed: state.speed,
+                        weight: blend_space_weight,
+                    },
+                    ChildOf(vrm),
+                ))
+                .id();
+            Some(blend_space)
+        }
+    }
+}
+
+fn update_fade(
+    commands: &mut Commands,
+    state: &mut VrmaStateMachineState,
+    blend_spaces: &mut Query<(&mut VrmaBlendSpace, Option<&VrmaBlendSpaceTime>)>,
+    delta: f32,
+) {
+    let Some(fade) = state.fade.as_mut() else {
+        return;
+    };
+    fade.elapsed += delta;
+    if fade.elapsed < fade.duration {
+        apply_fade_weights(state.blend_space, fade, blend_spaces);
+        return;
+    }
+    if let Some(fade) = state.fade.take() {
+        finish_fade(commands, fade.from);
+    }
+    if let Some(mut blend_space) = state
+        .blend_space
+        .and_then(|entity| blend_spaces.get_mut(entity).ok())
+    {
+        blend_space.0.weight = 1.;
+    }
+}
+
+/// Sets the weights of the blend spaces so that the ratio of the next state is the progress of the crossfade.
+///
+/// The animation played by [`PlayVrma`] has the weight `1.0`, so the weight of the blend space is converted to be relative to it.
+fn apply_fade_weights(
+    next_blend_space: Option<Entity>,
+    fade: &Fade,
+    blend_spaces: &mut Query<(&mut VrmaBlendSpace, Option<&VrmaBlendSpaceTime>)>,
+) {
+    let progress = (fade.elapsed / fade.duration).clamp(0., 1.);
+    let (next_weight, from_weight) = match (next_blend_space, fade.from) {
+        (Some(_), Motion::BlendSpace(_)) => (progress, 1. - progress),
+        (Some(_), Motion::Clip(_)) => (progress / (1. - progress).max(f32::EPSILON), 1.),
+        (None, _) => (1., (1. - progress) / progress.max(f32::EPSILON)),
+    };
+    if let Some(mut blend_space) =
+        next_blend_space.and_then(|entity| blend_spaces.get_mut(entity).ok())
+    {
+        blend_space.0.weight = next_weight;
+    }
+    if let Motion::BlendSpace(entity) = fade.from
+        && let Ok((mut blend_space, _)) = blend_spaces.get_mut(entity)
+    {
+        blend_space.weight = from_weight;
+    }
+}
+
+/// Stops the motion of the previous state after the crossfade.
+///
+/// The motion may have been despawned with the VRM, so this does nothing for the despawned entities.
+fn finish_fade(
+    commands: &mut Commands,
+    from: Motion,
+) {
+    match from {
+        Motion::Clip(vrma) => {
+            commands.trigger_targets(StopVrma, vrma);
+        }
+        Motion::BlendSpace(blend_space) => {
+            commands.entity(blend_space).try_despawn();
+        }
+    }
+}
+
+/// Stops the motions of the state machine when [`VrmaStateMachineHandle`] is removed or the VRM is despawned.
+fn stop_state_machine(
+    trigger: Trigger<OnRemove, VrmaStateMachineHandle>,
+    mut commands: Commands,
+    vrms: Query<(&VrmaStateMachineHandle, &VrmaStateMachineState)>,
+    state_machines: Res<Assets<VrmaStateMachine>>,
+    vrmas: Query<(Entity, &ChildOf, &VrmaPath)>,
+) {
+    let vrm = trigger.target();
+    let Ok((handle, state)) = vrms.get(vrm) else {
+        return;
+    };
+    if let Some(fade) = state.fade.as_ref() {
+        finish_fade(&mut commands, fade.from);
+    }
+    if let Some(blend_space) = state.blend_space {
+        finish_fade(&mut commands, Motion::BlendSpace(blend_space));
+    } else if let Some(VrmaMotion::Clip { vrma, .. }) = state_machines
+        .get(handle.0.id())
+        .and_then(|state_machine| state_machine.state(&state.current))
+        .map(|state| &state.motion)
+        && let Some((vrma, ..)) = vrmas
+            .iter()
+            .find(|(_, ChildOf(parent), path)| *parent == vrm && path.0 == *vrma)
+    {
+        finish_fade(&mut commands, Motion::Clip(vrma));
+    }
+    commands.entity(vrm).try_remove::<VrmaStateMachineState>();
+}
+
+fn blend_space_parameter(
+    parameters: &VrmaStateMachineParameters,
+    x: &str,
+    y: Option<&str>,
+) -> Vec2 {
+    Vec2::new(
+        parameters.float(x),
+        y.map(|y| parameters.float(y)).unwrap_or_default(),
+    )
+}
+
+fn motion_vrmas(motion: &VrmaMotion) -> impl Iterator<Item = &Path> {
+    let paths: Vec<&Path> = match motion {
+        VrmaMotion::Clip { vrma, .. } => vec![vrma],
+        VrmaMotion::BlendSpace { samples, .. } => {
+            samples.iter().map(|sample| sample.vrma.as_path()).collect()
+        }
+    };
+    paths.into_iter()
+}
+
+/// Returns the normalized time of the clip, which is increased by `1.0` each loop.
+fn normalized_clip_time(
+    completions: u32,
+    finished: bool,
+    seek_time: f32,
+    duration: f32,
+) -> f32 {
+    if finished || duration <= 0. {
+        completions as f32
+    } else {
+        completions as f32 + seek_time / duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::tests::{TestVrm, spawn_vrm_with_vrmas, test_app};
+    use crate::vrma::VrmaPath;
+    use crate::vrma::animation::blend_space::VrmaBlendSpacePlugin;
+    use crate::vrma::animation::play::VrmaAnimationPlayPlugin;
+    use crate::vrma::animation::state_machine::VrmaStateMachinePlugin;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    const LOCOMOTION: &str = r#"(
+        initial_state: "idle",
+        states: [
+            (name: "idle", motion: Clip(vrma: "idle.vrma", repeat: true)),
+            (
+                name: "walk",
+                motion: BlendSpace(
+                    x: "speed",
+                    samples: [
+                        (vrma: "walk.vrma", position: (0.0, 0.0)),
+                        (vrma: "run.vrma", position: (1.0, 0.0)),
+                    ],
+                ),
+            ),
+            (
+                name: "run",
+                motion: BlendSpace(
+                    x: "speed",
+                    samples: [
+                        (vrma: "walk.vrma", position: (-1.0, 0.0)),
+                        (vrma: "run.vrma", position: (0.0, 0.0)),
+                    ],
+                ),
+            ),
+        ],
+        transitions: [
+            (from: Some("idle"), to: "walk", conditions: [Trigger("walk")], duration: 0.4),
+            (from: Some("walk"), to: "run", conditions: [Trigger("run")], duration: 0.4),
+            (to: "idle", conditions: [Trigger("stop")], duration: 0.4),
+        ],
+    )"#;
+
+    /// Spawns a VRM that has the VRMAs of `names` and starts the state machine.
+    fn setup(
+        state_machine: &str,
+        names: &[&str],
+    ) -> (App, Entity, Vec<AnimationNodeIndex>) {
+        let mut app = test_app();
+        app.add_plugins((
+            AnimationPlugin,
+            VrmaAnimationPlayPlugin,
+            VrmaBlendSpacePlugin,
+            VrmaStateMachinePlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )));
+
+        let clips = names
+            .iter()
+            .map(|_| {
+                let mut clip = AnimationClip::default();
+                clip.set_duration(1.);
+                app.world_mut()
+                    .resource_mut::<Assets<AnimationClip>>()
+                    .add(clip)
+            })
+            .collect::<Vec<_>>();
+        let (graph, node_indices) = AnimationGraph::from_clips(clips);
+        let graph = app
+            .world_mut()
+            .resource_mut::<Assets<AnimationGraph>>()
+            .add(graph);
+        let state_machine = app
+            .world_mut()
+            .resource_mut::<Assets<VrmaStateMachine>>()
+            .add(ron::from_str::<VrmaStateMachine>(state_machine).unwrap());
+
+        let TestVrm { vrm, vrmas, .. } = spawn_vrm_with_vrmas(&mut app, graph, &node_indices);
+        app.world_mut()
+            .entity_mut(vrm)
+            .insert(VrmaStateMachineHandle(state_machine));
+        for (name, vrma) in names.iter().zip(vrmas) {
+            app.world_mut()
+                .entity_mut(vrma)
+                .insert(VrmaPath(format!("{name}.vrma").into()));
+        }
+        app.update();
+        app.update();
+        (app, vrm, node_indices)
+    }
+
+    fn set_trigger(
+        app: &mut App,
+        vrm: Entity,
+        name: &str,
+    ) {
+        app.world_mut()
+            .get_mut::<VrmaStateMachineParameters>(vrm)
+            .unwrap()
+            .set_trigger(name);
+    }
+
+    fn current(
+        app: &App,
+        vrm: Entity,
+    ) -> Option<String> {
+        app.world()
+            .get::<VrmaStateMachineState>(vrm)
+            .map(|state| state.current().to_string())
+    }
+
+    /// Returns the weight of the blend space named after the state.
+    fn blend_space_weight(
+        app: &mut App,
+        name: &str,
+    ) -> Option<f32> {
+        app.world_mut()
+            .query::<(&Name, &VrmaBlendSpace)>()
+            .iter(app.world())
+            .find(|(n, _)| n.as_str() == name)
+            .map(|(_, blend_space)| blend_space.weight)
+    }
+
+    fn clip_weight(
+        app: &mut App,
+        node_index: AnimationNodeIndex,
+    ) -> Option<f32> {
+        app.world_mut()
+            .query::<&AnimationPlayer>()
+            .single(app.world())
+            .unwrap()
+            .animation(node_index)
+            .map(|animation| animation.weight())
+    }
+
+    #[test]
+    fn transition_by_trigger_and_exit_time() {
+        let (mut app, vrm, _) = setup(
+            r#"(
+                initial_state: "idle",
+                states: [
+                    (name: "idle", motion: Clip(vrma: "idle.vrma", repeat: true)),
+                    (name: "wave", motion: Clip(vrma: "wave.vrma")),
+                ],
+                transitions: [
+                    (to: "wave", conditions: [Trigger("wave")], duration: 0.2),
+                    (from: Some("wave"), to: "idle", exit_time: Some(1.0), duration: 0.2),
+                ],
+            )"#,
+            &["idle", "wave"],
+        );
+        assert_eq!(current(&app, vrm).as_deref(), Some("idle"));
+
+        set_trigger(&mut app, vrm, "wave");
+        app.update();
+        assert_eq!(current(&app, vrm).as_deref(), Some("wave"));
+        assert!(
+            !app.world()
+                .get::<VrmaStateMachineParameters>(vrm)
+                .unwrap()
+                .is_triggered("wave")
+        );
+
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(current(&app, vrm).as_deref(), Some("wave"));
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(current(&app, vrm).as_deref(), Some("idle"));
+    }
+
+    #[test]
+    fn crossfade_clip_to_blend_space() {
+        let (mut app, vrm, node_indices) = setup(LOCOMOTION, &["idle", "walk", "run"]);
+        let idle = node_indices[0];
+
+        set_trigger(&mut app, vrm, "walk");
+        app.update();
+        assert_eq!(current(&app, vrm).as_deref(), Some("walk"));
+        assert_eq!(blend_space_weight(&mut app, "walk"), Some(0.));
+        assert_eq!(clip_weight(&mut app, idle), Some(1.));
+
+        // The clip keeps its weight, so the blend space has 25% of the total weight.
+        app.update();
+        let weight = blend_space_weight(&mut app, "walk").unwrap();
+        assert!((weight - 1. / 3.).abs() < 1e-4, "{weight}");
+        assert_eq!(clip_weight(&mut app, idle), Some(1.));
+
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(blend_space_weight(&mut app, "walk"), Some(1.));
+        assert_eq!(clip_weight(&mut app, idle), None);
+    }
+
+    #[test]
+    fn crossfade_between_blend_spaces() {
+        let (mut app, vrm, _) = setup(LOCOMOTION, &["idle", "walk", "run"]);
+        set_trigger(&mut app, vrm, "walk");
+        for _ in 0..6 {
+            app.update();
+        }
+
+        set_trigger(&mut app, vrm, "run");
+        app.update();
+        assert_eq!(current(&app, vrm).as_deref(), Some("run"));
+        assert_eq!(blend_space_weight(&mut app, "walk"), Some(1.));
+        assert_eq!(blend_space_weight(&mut app, "run"), Some(0.));
+
+        app.update();
+        let walk = blend_space_weight(&mut app, "walk").unwrap();
+        let run = blend_space_weight(&mut app, "run").unwrap();
+        assert!((walk - 0.75).abs() < 1e-4, "{walk}");
+        assert!((run - 0.25).abs() < 1e-4, "{run}");
+
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(blend_space_weight(&mut app, "walk"), None);
+        assert_eq!(blend_space_weight(&mut app, "run"), Some(1.));
+    }
+
+    #[test]
+    fn crossfade_blend_space_to_clip_by_any_state_transition() {
+        let (mut app, vrm, node_indices) = setup(LOCOMOTION, &["idle", "walk", "run"]);
+        let idle = node_indices[0];
+        set_trigger(&mut app, vrm, "walk");
+        for _ in 0..6 {
+            app.update();
+        }
+
+        set_trigger(&mut app, vrm, "stop");
+        app.update();
+        assert_eq!(current(&app, vrm).as_deref(), Some("idle"));
+
+        // The clip has the weight of 1, so the blend space has 75% of the total weight.
+        app.update();
+        let weight = blend_space_weight(&mut app, "walk").unwrap();
+        assert!((weight - 3.).abs() < 1e-4, "{weight}");
+        assert_eq!(clip_weight(&mut app, idle), Some(1.));
+
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(blend_space_weight(&mut app, "walk"), None);
+        assert_eq!(clip_weight(&mut app, idle), Some(1.));
+    }
+
+    #[test]
+    fn stop_motions_when_handle_is_removed() {
+        let (mut app, vrm, node_indices) = setup(LOCOMOTION, &["idle", "walk", "run"]);
+        set_trigger(&mut app, vrm, "walk");
+        app.update();
+        app.update();
+
+        app.world_mut()
+            .entity_mut(vrm)
+            .remove::<VrmaStateMachineHandle>();
+        app.update();
+        assert!(app.world().get::<VrmaStateMachineState>(vrm).is_none());
+        assert_eq!(blend_space_weight(&mut app, "walk"), None);
+        assert_eq!(clip_weight(&mut app, node_indices[0]), None);
+        app.update();
+        let player = app
+            .world_mut()
+            .query::<&AnimationPlayer>()
+            .single(app.world())
+            .unwrap();
+        assert!(player.playing_animations().next().is_none());
+    }
+
+    #[test]
+    fn despawn_vrm_while_crossfading() {
+        let (mut app, vrm, _) = setup(LOCOMOTION, &["idle", "walk", "run"]);
+        set_trigger(&mut app, vrm, "walk");
+        app.update();
+        app.update();
+
+        app.world_mut().entity_mut(vrm).despawn();
+        app.update();
+        assert_eq!(blend_space_weight(&mut app, "walk"), None);
+    }
+}
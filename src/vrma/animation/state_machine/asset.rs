@@ -0,0 +1,352 @@
+//! The asset of the animation state machine and its loader.
+
+use crate::error::AppResult;
+use crate::vrma::VrmaClipSelector;
+use anyhow::ensure;
+use bevy::app::{App, Plugin};
+use bevy::asset::io::Reader;
+use bevy::asset::{Asset, AssetApp, AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub(super) struct VrmaStateMachineAssetPlugin;
+
+impl Plugin for VrmaStateMachineAssetPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_asset::<VrmaStateMachine>()
+            .register_asset_loader(VrmaStateMachineLoader);
+    }
+}
+
+/// The animation state machine of VRM, which can be loaded from a `.state_machine.ron` file.
+///
+/// The VRMAs are referenced by their asset paths, which are the same as [`VrmaPath`](crate::prelude::VrmaPath).
+/// The loading fails if a state is not found by its name, or a time of the transitions is negative or not finite.
+///
+/// ```ron
+/// (
+///     initial_state: "locomotion",
+///     states: [
+///         (
+///             name: "locomotion",
+///             motion: BlendSpace(
+///                 x: "speed",
+///                 samples: [
+///                     (vrma: "vrma/idle.vrma", position: (0.0, 0.0)),
+///                     (vrma: "vrma/walk.vrma", position: (1.5, 0.0)),
+///                 ],
+///             ),
+///         ),
+///         (
+///             name: "jump",
+///             motion: Clip(vrma: "vrma/jump.vrma"),
+///         ),
+///     ],
+///     transitions: [
+///         (to: "jump", conditions: [Trigger("jump")], duration: 0.1),
+///         (from: Some("jump"), to: "locomotion", exit_time: Some(0.9), duration: 0.3),
+///     ],
+/// )
+/// ```
+#[derive(Asset, TypePath, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VrmaStateMachine {
+    /// The name of the state entered first.
+    pub initial_state: String,
+    pub states: Vec<VrmaState>,
+    /// The transitions are evaluated in order, and the first one satisfied is taken.
+    #[serde(default)]
+    pub transitions: Vec<VrmaStateTransition>,
+}
+
+impl VrmaStateMachine {
+    /// Returns the state with the name.
+    pub fn state(
+        &self,
+        name: &str,
+    ) -> Option<&VrmaState> {
+        self.states.iter().find(|state| state.name == name)
+    }
+
+    /// Checks that the states referenced by name exist and the times are valid.
+    fn validate(&self) -> AppResult {
+        let ensure_state = |name: &str| -> AppResult {
+            ensure!(self.state(name).is_some(), "Not found the state {name}");
+            Ok(())
+        };
+        ensure_state(&self.initial_state)?;
+        for state in self.states.iter() {
+            ensure!(
+                state.speed.is_finite(),
+                "The speed of the state {} must be finite",
+                state.name
+            );
+        }
+        for transition in self.transitions.iter() {
+            if let Some(from) = transition.from.as_ref() {
+                ensure_state(from)?;
+            }
+            ensure_state(&transition.to)?;
+            ensure!(
+                transition.duration.is_finite() && 0.0 <= transition.duration,
+                "The duration of the transition to {} must be a non-negative finite number",
+                transition.to
+            );
+            if let Some(exit_time) = transition.exit_time {
+                ensure!(
+                    exit_time.is_finite() && 0.0 <= exit_time,
+                    "The exit time of the transition to {} must be a non-negative finite number",
+                    transition.to
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A state of [`VrmaStateMachine`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VrmaState {
+    pub name: String,
+    pub motion: VrmaMotion,
+    /// The playback speed of the motion.
+    /// Default is `1.0`.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+/// The animation played in [`VrmaState`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VrmaMotion {
+    /// Plays an animation of VRMA with [`PlayVrma`](crate::prelude::PlayVrma).
+    Clip {
+        /// The asset path of VRMA.
+        vrma: PathBuf,
+        /// Default is the first animation.
+        #[serde(default, with = "clip_selector")]
+        clip: VrmaClipSelector,
+        /// Whether to repeat the animation forever.
+        /// Default is `false`.
+        #[serde(default)]
+        repeat: bool,
+    },
+    /// Plays a [`VrmaBlendSpace`](crate::prelude::VrmaBlendSpace) whose coordinates are the float parameters.
+    BlendSpace {
+        samples: Vec<VrmaMotionSample>,
+        /// The name of the float parameter used as the x coordinate.
+        x: String,
+        /// The name of the float parameter used as the y coordinate.
+        /// The y coordinate is `0.0` if not specified.
+        #[serde(default)]
+        y: Option<String>,
+    },
+}
+
+/// A sample of [`VrmaMotion::BlendSpace`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VrmaMotionSample {
+    /// The asset path of VRMA.
+    pub vrma: PathBuf,
+    /// Default is the first animation.
+    #[serde(default, with = "clip_selector")]
+    pub clip: VrmaClipSelector,
+    /// The coordinates in the blend space.
+    pub position: (f32, f32),
+}
+
+/// A transition between the states of [`VrmaStateMachine`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VrmaStateTransition {
+    /// The name of the source state.
+    /// If not specified, the transition can be taken from any state except the destination.
+    #[serde(default)]
+    pub from: Option<String>,
+    /// The name of the destination state.
+    pub to: String,
+    /// All conditions must be satisfied to take the transition.
+    #[serde(default)]
+    pub conditions: Vec<VrmaCondition>,
+    /// The normalized time of the source state that must be reached before the transition.
+    ///
+    /// `1.0` is the end of the first loop, and `2.0` is the end of the second loop.
+    #[serde(default)]
+    pub exit_time: Option<f32>,
+    /// The duration of the crossfade in seconds.
+    #[serde(default)]
+    pub duration: f32,
+}
+
+/// A condition on the parameters of [`VrmaStateMachineParameters`](crate::prelude::VrmaStateMachineParameters).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VrmaCondition {
+    /// The float parameter is greater than the value.
+    Greater(String, f32),
+    /// The float parameter is less than the value.
+    Less(String, f32),
+    /// The bool parameter is `true`.
+    True(String),
+    /// The bool parameter is `false`.
+    False(String),
+    /// The trigger parameter is set.
+    /// The trigger is reset when the transition is taken.
+    Trigger(String),
+}
+
+#[inline]
+fn default_speed() -> f32 {
+    1.0
+}
+
+/// The format of [`VrmaClipSelector`] in the RON file.
+///
+/// This is separated from [`VrmaClipSelector`] so that its serialization can be enabled by the `serde` feature.
+mod clip_selector {
+    use crate::vrma::VrmaClipSelector;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    enum ClipSelector {
+        Index(usize),
+        Name(String),
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        selector: &VrmaClipSelector,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match selector {
+            VrmaClipSelector::Index(index) => ClipSelector::Index(*index),
+            VrmaClipSelector::Name(name) => ClipSelector::Name(name.clone()),
+        }
+        .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<VrmaClipSelector, D::Error> {
+        Ok(match ClipSelector::deserialize(deserializer)? {
+            ClipSelector::Index(index) => VrmaClipSelector::Index(index),
+            ClipSelector::Name(name) => VrmaClipSelector::Name(name),
+        })
+    }
+}
+
+struct VrmaStateMachineLoader;
+
+impl AssetLoader for VrmaStateMachineLoader {
+    type Asset = VrmaStateMachine;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &Self::Settings,
+        _: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let state_machine: VrmaStateMachine = ron::de::from_bytes(&bytes)?;
+        state_machine.validate()?;
+        Ok(state_machine)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["state_machine.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn deserialize_from_ron() {
+        let state_machine: VrmaStateMachine = ron::from_str(
+            r#"(
+                initial_state: "locomotion",
+                states: [
+                    (
+                        name: "locomotion",
+                        motion: BlendSpace(
+                            x: "speed",
+                            samples: [
+                                (vrma: "vrma/idle.vrma", position: (0.0, 0.0)),
+                                (vrma: "vrma/walk.vrma", clip: Name("walk"), position: (1.5, 0.0)),
+                            ],
+                        ),
+                    ),
+                    (name: "jump", motion: Clip(vrma: "vrma/jump.vrma"), speed: 2.0),
+                ],
+                transitions: [
+                    (to: "jump", conditions: [Trigger("jump")], duration: 0.1),
+                    (from: Some("jump"), to: "locomotion", exit_time: Some(0.9)),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        let jump = state_machine.state("jump").unwrap();
+        assert_eq!(jump.speed, 2.0);
+        assert_eq!(
+            jump.motion,
+            VrmaMotion::Clip {
+                vrma: "vrma/jump.vrma".into(),
+                clip: VrmaClipSelector::Index(0),
+                repeat: false,
+            }
+        );
+        assert_eq!(state_machine.state("locomotion").unwrap().speed, 1.0);
+        assert_eq!(state_machine.transitions[0].from, None);
+        assert_eq!(state_machine.transitions[1].exit_time, Some(0.9));
+        assert_eq!(state_machine.transitions[1].duration, 0.0);
+    }
+
+    #[test]
+    fn validate_references_and_durations() {
+        let state_machine = |initial_state: &str, transition: &str| {
+            ron::from_str::<VrmaStateMachine>(&format!(
+                r#"(
+                    initial_state: "{initial_state}",
+                    states: [
+                        (name: "idle", motion: Clip(vrma: "vrma/idle.vrma")),
+                        (name: "jump", motion: Clip(vrma: "vrma/jump.vrma")),
+                    ],
+                    transitions: [{transition}],
+                )"#
+            ))
+            .unwrap()
+        };
+
+        assert!(
+            state_machine("idle", r#"(from: Some("jump"), to: "idle", duration: 0.1)"#)
+                .validate()
+                .is_ok()
+        );
+        assert!(state_machine("run", "").validate().is_err());
+        assert!(
+            state_machine("idle", r#"(from: Some("run"), to: "idle")"#)
+                .validate()
+                .is_err()
+        );
+        assert!(state_machine("idle", r#"(to: "run")"#).validate().is_err());
+        assert!(
+            state_machine("idle", r#"(to: "jump", duration: -0.1)"#)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            state_machine("idle", r#"(to: "jump", duration: inf)"#)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            state_machine("idle", r#"(to: "jump", exit_time: Some(NaN))"#)
+                .validate()
+                .is_err()
+        );
+    }
+}
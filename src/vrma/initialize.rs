@@ -4,9 +4,14 @@ use crate::error::vrm_error;
 use crate::vrm::Initialized;
 use crate::vrm::humanoid_bone::HumanoidBoneRegistry;
 use crate::vrma::animation::expressions::VrmaExpressionNames;
+use crate::vrma::animation::markers::read_markers;
 use crate::vrma::gltf::extensions::VrmaExtensions;
 use crate::vrma::loader::VrmaAsset;
-use crate::vrma::{LoadedVrma, VrmAnimationClipHandle, Vrma, VrmaDuration, VrmaHandle, VrmaPath};
+use crate::vrma::{
+    LoadedVrma, VrmAnimationClipHandles, Vrma, VrmaClip, VrmaClips, VrmaDuration, VrmaHandle,
+    VrmaPath,
+};
+use bevy::gltf::Gltf;
 use bevy::gltf::GltfNode;
 use bevy::prelude::*;
 use bevy::scene::SceneRoot;
@@ -60,21 +65,38 @@ fn spawn_vrma(
                 continue;
             }
         };
-        let Some(animation_clip_handle) = vrma.gltf.animations.first() else {
+        if vrma.gltf.animations.is_empty() {
             vrm_error!("[VRMA] Not found vrma animations in {name}");
             continue;
-        };
-        let Some(animation_clip) = clip_assets.get(animation_clip_handle).cloned() else {
+        }
+        let Some(animation_clips) = vrma
+            .gltf
+            .animations
+            .iter()
+            .map(|handle| clip_assets.get(handle).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
             vrm_error!("[VRMA] Not found animation clip for {name}");
             continue;
         };
-        let animation_clip_handle = clip_assets.add(animation_clip);
+        let clips = obtain_vrma_clips(&vrma.gltf, &animation_clips);
+        let duration = clips
+            .iter()
+            .map(|clip| clip.duration)
+            .max()
+            .unwrap_or_default();
+        // The clips are copied because their curves are retargeted to each VRM.
+        let animation_clip_handles = animation_clips
+            .into_iter()
+            .map(|clip| clip_assets.add(clip))
+            .collect();
         commands.entity(handle_entity).insert((
             Vrma,
             Name::new(name),
-            VrmAnimationClipHandle(animation_clip_handle.clone()),
+            VrmAnimationClipHandles(animation_clip_handles),
             SceneRoot(scene_root),
-            VrmaDuration(obtain_vrma_duration(&clip_assets, &vrma.gltf.animations)),
+            VrmaDuration(duration),
+            VrmaClips(clips),
             VrmaPath(vrma_path),
             VrmaExpressionNames::new(&extensions),
             HumanoidBoneRegistry::new(
@@ -86,16 +108,24 @@ fn spawn_vrma(
     }
 }
 
-fn obtain_vrma_duration(
-    assets: &Assets<AnimationClip>,
-    handles: &[Handle<AnimationClip>],
-) -> Duration {
-    let duration = handles
+fn obtain_vrma_clips(
+    gltf: &Gltf,
+    animation_clips: &[AnimationClip],
+) -> Vec<VrmaClip> {
+    gltf.animations
         .iter()
-        .filter_map(|handle| assets.get(handle))
-        .map(|clip| clip.duration() as f64)
-        .fold(0., |v1, v2| v2.max(v1));
-    Duration::from_secs_f64(duration)
+        .zip(animation_clips)
+        .zip(read_markers(gltf))
+        .map(|((handle, clip), markers)| VrmaClip {
+            name: gltf
+                .named_animations
+                .iter()
+                .find(|(_, named)| *named == handle)
+                .map(|(name, _)| name.to_string()),
+            duration: Duration::from_secs_f32(clip.duration()),
+            markers,
+        })
+        .collect()
 }
 
 fn trigger_loaded(